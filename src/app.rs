@@ -2,188 +2,182 @@ use wasm_bindgen::{JsCast, UnwrapThrowExt};
 use web_sys::*;
 use yew::prelude::*;
 
-use crate::calculate::calculate_weights;
+use war_core::{
+    analyze_sensitivity, blend_histories, find_minimum_force, infer_modifiers, resume_battle,
+    run_campaign, run_mixed_battle, run_until_resolved, simulate_battles, solve_final_outcome_from,
+    solve_retreat_policy, summarize_history, sweep_matchups, ArmySize, Campaign, CampaignLeg,
+    CampaignReport, Composition, EndingRounds, Estimate, FogOfWar, ForceTarget, MixedWeights,
+    OutcomeStats, PolicyObjective, Posterior, Reinforcement, ResolutionOrder, RetreatPolicy,
+    RetreatThreshold, RoundSummary, Sensitivity, Side, SimulationReport, Sweep, SweepRange, Toggle,
+    UnitType, WarOdds, WarWeights,
+};
 
-pub const MAX_SOLDIERS: usize = 100;
+use crate::charts::{
+    get_ending_rounds_chart_node, get_heatmap_node, get_history_chart_node, get_policy_node,
+};
 
-pub struct WarOdds {
-    pub base_chance: f64,
-    pub commander_bonus: f64,
-    pub blessing_bonus: f64,
-    pub fortified_def_bonus: f64,
-    pub claimed_def_bonus: f64,
-    pub city_def_bonus: f64,
-    pub archer_attack_malus: f64,
-    pub archer_defense_malus: f64,
-    pub elite_attack_bonus: f64,
-    pub elite_defense_bonus: f64,
-    pub attacker_present: bool,
-    pub defender_present: bool,
-    pub attacker_blessed: bool,
-    pub defender_blessed: bool,
-    pub defender_fortified: bool,
-    pub attacker_claimed: bool,
-    pub defender_claimed: bool,
-    pub attacker_city: bool,
-    pub defender_city: bool,
-    pub attacker_archers: bool,
-    pub defender_archers: bool,
-    pub attacker_elites: bool,
-    pub defender_elites: bool,
-    pub round_count: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScoutingKind {
+    Exact,
+    Range,
+    Triangular,
+    Weighted,
 }
 
-impl Default for WarOdds {
-    fn default() -> Self {
-        WarOdds {
-            base_chance: 10.,
-            commander_bonus: 1.,
-            blessing_bonus: 2.,
-            fortified_def_bonus: 1.,
-            claimed_def_bonus: 1.,
-            city_def_bonus: 2.,
-            attacker_present: true,
-            defender_present: false,
-            attacker_blessed: true,
-            defender_blessed: true,
-            defender_fortified: false,
-            attacker_claimed: false,
-            defender_claimed: false,
-            attacker_city: false,
-            defender_city: false,
-            attacker_archers: false,
-            defender_archers: false,
-            attacker_elites: false,
-            defender_elites: false,
-            archer_attack_malus: 1.,
-            archer_defense_malus: 1.,
-            elite_attack_bonus: 1.,
-            elite_defense_bonus: 1.,
-            round_count: 20,
-        }
-    }
+// The raw scouting inputs for one side, kept around so switching between
+// kinds of estimate doesn't lose what was typed.
+struct Scouting {
+    kind: ScoutingKind,
+    min: usize,
+    mode: usize,
+    max: usize,
+    weights: String,
 }
 
-impl WarOdds {
-    pub fn get_attacker_rate(&self) -> f64 {
-        let mut rate = self.base_chance;
-        if self.attacker_present {
-            rate += self.commander_bonus;
-        }
-        if self.attacker_blessed {
-            rate += self.blessing_bonus;
-        }
-        if self.defender_claimed {
-            rate -= self.claimed_def_bonus;
-        }
-        if self.defender_present && self.defender_fortified {
-            rate -= self.fortified_def_bonus;
-        }
-        if self.defender_city {
-            rate -= self.city_def_bonus;
-        }
-        if self.defender_archers {
-            rate += self.archer_defense_malus;
-        }
-        if self.attacker_archers {
-            rate -= self.archer_attack_malus;
-        }
-        if self.attacker_elites {
-            rate += self.elite_attack_bonus;
-        }
-        if self.defender_elites {
-            rate -= self.elite_defense_bonus;
-        }
-        rate / 100.
-    }
-
-    pub fn get_defender_rate(&self) -> f64 {
-        let mut rate = self.base_chance;
-        if self.defender_present {
-            rate += self.commander_bonus;
-        }
-        if self.defender_blessed {
-            rate += self.blessing_bonus;
-        }
-        if self.attacker_claimed {
-            rate -= self.claimed_def_bonus;
-        }
-        if self.attacker_city {
-            rate -= self.city_def_bonus;
-        }
-        if self.attacker_archers {
-            rate += self.archer_defense_malus;
-        }
-        if self.defender_archers {
-            rate -= self.archer_attack_malus;
-        }
-        if self.defender_elites {
-            rate += self.elite_attack_bonus;
-        }
-        if self.attacker_elites {
-            rate -= self.elite_defense_bonus;
-        }
-        rate / 100.
-    }
-}
-
-pub const WEIGHT_COUNT: usize = (MAX_SOLDIERS + 1) * (MAX_SOLDIERS + 1);
-
-#[derive(Debug)]
-pub struct WarWeights(pub [f64; WEIGHT_COUNT]);
-
-impl Default for WarWeights {
-    fn default() -> Self {
-        Self([0.; WEIGHT_COUNT])
-    }
-}
-
-impl WarWeights {
-    pub fn slot_for(attackers: usize, defenders: usize) -> usize {
-        attackers * (MAX_SOLDIERS + 1) + defenders
-    }
-
-    pub fn get_attackers_winning_results(&self) -> [f64; MAX_SOLDIERS + 1] {
-        let mut results = [0.; MAX_SOLDIERS + 1];
-        for j in 0..=MAX_SOLDIERS {
-            results[j] = self.0[Self::slot_for(j, 0)];
-        }
-        results
-    }
-
-    pub fn get_defenders_winning_results(&self) -> [f64; MAX_SOLDIERS + 1] {
-        let mut results = [0.; MAX_SOLDIERS + 1];
-        for i in 0..=MAX_SOLDIERS {
-            results[i] = self.0[Self::slot_for(0, i)];
+impl Scouting {
+    fn new(min: usize, mode: usize, max: usize) -> Self {
+        Self {
+            kind: ScoutingKind::Exact,
+            min,
+            mode,
+            max,
+            weights: format!("{}:1, {}:2, {}:1", min, mode, max),
         }
-        results
     }
 
-    pub fn get_odds_of_no_win(&self) -> f64 {
-        let mut odds = 0.;
-        for i in 1..=MAX_SOLDIERS {
-            for j in 1..=MAX_SOLDIERS {
-                odds += self.0[Self::slot_for(i, j)];
-            }
+    // Falls back to the exact count when the estimate has no weight at all.
+    fn get_army_size(&self, exact: f64) -> ArmySize {
+        let size = match self.kind {
+            ScoutingKind::Exact => return ArmySize::Exact(exact as usize),
+            ScoutingKind::Range => ArmySize::Uniform {
+                min: self.min,
+                max: self.max,
+            },
+            ScoutingKind::Triangular => ArmySize::Triangular {
+                min: self.min,
+                mode: self.mode,
+                max: self.max,
+            },
+            ScoutingKind::Weighted => ArmySize::Weighted(
+                self.weights
+                    .split(',')
+                    .filter_map(|entry| {
+                        let (size, weight) = entry.split_once(':')?;
+                        Some((size.trim().parse().ok()?, weight.trim().parse().ok()?))
+                    })
+                    .collect(),
+            ),
+        };
+        if size.get_distribution().is_empty() {
+            ArmySize::Exact(exact as usize)
+        } else {
+            size
         }
-        odds
     }
 }
 
 pub struct WarModel {
     odds: WarOdds,
+    fog: FogOfWar,
     starting_attackers: f64,
     starting_defenders: f64,
+    in_progress: bool,
+    attacker_scouting: Scouting,
+    defender_scouting: Scouting,
+    rounds_elapsed: usize,
+    // Strength at the start of a battle in progress, which percentage retreat
+    // thresholds are measured against.
+    started_with: (usize, usize),
+    reinforcement: Reinforcement,
     weights: Option<WarWeights>,
+    scenario_count: usize,
+    exact_weights: Option<WarWeights>,
+    history: Option<Vec<RoundSummary>>,
+    run_until_resolved: bool,
+    resolve_tolerance: f64,
+    solve_unlimited: bool,
+    unlimited_outcome: Option<WarWeights>,
+    observations: String,
+    posterior: Option<Option<Posterior>>,
+    prediction: Option<WarWeights>,
+    campaign_legs: Vec<CampaignLeg>,
+    campaign_report: Option<CampaignReport>,
+    force_target: ForceTarget,
+    force_result: Option<Option<(usize, f64)>>,
+    sensitivities: Option<Vec<Sensitivity>>,
+    policy_side: Side,
+    policy_objective: PolicyObjective,
+    policy_round: usize,
+    policy: Option<RetreatPolicy>,
+    mixed_attackers: Composition,
+    mixed_defenders: Composition,
+    mixed_outcome: Option<MixedWeights>,
+    sweep_attackers: SweepRange,
+    sweep_defenders: SweepRange,
+    sweep: Option<Sweep>,
+    simulation_seed: u64,
+    simulation: Option<SimulationReport>,
 }
 
 impl Default for WarModel {
     fn default() -> Self {
         Self {
             odds: WarOdds::default(),
+            fog: FogOfWar::default(),
             starting_attackers: 100.0,
             starting_defenders: 100.0,
+            in_progress: false,
+            attacker_scouting: Scouting::new(80, 100, 120),
+            defender_scouting: Scouting::new(80, 100, 120),
+            rounds_elapsed: 0,
+            started_with: (100, 100),
+            reinforcement: Reinforcement {
+                side: Side::Defender,
+                soldiers: 30,
+                round: 8,
+            },
             weights: None,
+            scenario_count: 1,
+            exact_weights: None,
+            history: None,
+            run_until_resolved: false,
+            resolve_tolerance: 0.0001,
+            solve_unlimited: false,
+            unlimited_outcome: None,
+            observations: "100/100, 93/91, 87/83".to_string(),
+            posterior: None,
+            prediction: None,
+            campaign_legs: Vec::new(),
+            campaign_report: None,
+            force_target: ForceTarget {
+                side: Side::Attacker,
+                opposing_soldiers: 100,
+                win_probability: 0.9,
+                min_survivors: 1,
+                max_soldiers: 400,
+            },
+            force_result: None,
+            sensitivities: None,
+            policy_side: Side::Attacker,
+            policy_objective: PolicyObjective::WinProbability { loss_penalty: 0.5 },
+            policy_round: 0,
+            policy: None,
+            mixed_attackers: Composition::new(60, 20, 20),
+            mixed_defenders: Composition::new(60, 20, 20),
+            mixed_outcome: None,
+            sweep_attackers: SweepRange {
+                min: 20,
+                max: 200,
+                step: 20,
+            },
+            sweep_defenders: SweepRange {
+                min: 20,
+                max: 200,
+                step: 20,
+            },
+            sweep: None,
+            simulation_seed: 0,
+            simulation: None,
         }
     }
 }
@@ -202,6 +196,31 @@ pub enum Msg {
     UpdateStartingAttackers(String),
     UpdateStartingDefenders(String),
     UpdateRoundCount(String),
+    UpdateVolleyRounds(String),
+    UpdateVolleyChance(String),
+    UpdateFrontage(String),
+    ToggleInProgress,
+    UpdateScoutingKind(Side, String),
+    UpdateRetreatKind(Side, String),
+    UpdateRetreatValue(Side, String),
+    UpdateScoutingMin(Side, String),
+    UpdateScoutingMode(Side, String),
+    UpdateScoutingMax(Side, String),
+    UpdateScoutingWeights(Side, String),
+    UpdateRoundsElapsed(String),
+    UpdateStartedWith(Side, String),
+    UpdateReinforcementSide(String),
+    UpdateReinforcementSoldiers(String),
+    UpdateReinforcementRound(String),
+    AddReinforcement,
+    RemoveReinforcement(usize),
+    UpdatePruneEpsilon(String),
+    UpdateKillTolerance(String),
+    UpdateResolutionOrder(String),
+    UpdateResolveTolerance(String),
+    ToggleRunUntilResolved,
+    ToggleSolveUnlimited,
+    UpdateToggleChance(Toggle, String),
     ToggleAttackerPresent,
     ToggleDefenderPresent,
     ToggleAttackerBlessed,
@@ -215,6 +234,33 @@ pub enum Msg {
     ToggleDefenderArchers,
     ToggleAttackerElites,
     ToggleDefenderElites,
+    UpdateForceSide(String),
+    UpdateForceWinProbability(String),
+    UpdateForceMinSurvivors(String),
+    UpdateForceMaxSoldiers(String),
+    FindMinimumForce,
+    AddCampaignLeg,
+    RemoveCampaignLeg(usize),
+    RunCampaign,
+    UpdateObservations(String),
+    InferModifiers,
+    AnalyzeSensitivity,
+    UpdatePolicySide(String),
+    UpdatePolicyObjective(String),
+    UpdatePolicyLossPenalty(String),
+    UpdatePolicyRound(String),
+    SolvePolicy,
+    UpdateComposition(Side, UnitType, String),
+    UpdateTargeting(Side, usize, String),
+    RunMixedBattle,
+    UpdateSweepAttackersMin(String),
+    UpdateSweepAttackersMax(String),
+    UpdateSweepAttackersStep(String),
+    UpdateSweepDefendersMin(String),
+    UpdateSweepDefendersMax(String),
+    UpdateSweepDefendersStep(String),
+    RunSweep,
+    Simulate,
     Calculate,
 }
 
@@ -228,28 +274,51 @@ fn get_value_from_input_event(e: InputEvent) -> String {
     target.value()
 }
 
+fn get_value_from_select_event(e: Event) -> String {
+    let target: HtmlSelectElement = e.target().unwrap_throw().dyn_into().unwrap_throw();
+    target.value()
+}
+
 impl Component for WarModel {
     type Message = Msg;
     type Properties = ();
 
-    fn create(ctx: &Context<Self>) -> Self {
+    fn create(_ctx: &Context<Self>) -> Self {
         Self::default()
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        if let Some(weights) = &self.weights {
+        if self.weights.is_some() {
             html!(
                 <div id="with_results">
                     {self.get_results_node(ctx)}
                     {self.get_settings_node(ctx)}
+                    {self.get_campaign_node(ctx)}
+                    {self.get_inference_node(ctx)}
+                    {self.get_solver_node(ctx)}
+                    {self.get_sensitivity_node(ctx)}
+                    {self.get_sweep_node(ctx)}
+                    {self.get_policy_panel_node(ctx)}
+                    {self.get_mixed_node(ctx)}
                 </div>
             )
         } else {
-            html!(<div id="without_results">{self.get_settings_node(ctx)}</div>)
+            html!(
+                <div id="without_results">
+                    {self.get_settings_node(ctx)}
+                    {self.get_campaign_node(ctx)}
+                    {self.get_inference_node(ctx)}
+                    {self.get_solver_node(ctx)}
+                    {self.get_sensitivity_node(ctx)}
+                    {self.get_sweep_node(ctx)}
+                    {self.get_policy_panel_node(ctx)}
+                    {self.get_mixed_node(ctx)}
+                </div>
+            )
         }
     }
 
-    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::UpdateBaseChance(val) => {
                 if let Ok(val) = val.parse::<f64>() {
@@ -316,170 +385,1108 @@ impl Component for WarModel {
                     self.odds.round_count = val;
                 }
             }
+            Msg::UpdateVolleyRounds(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.odds.volley_rounds = val;
+                }
+            }
+            Msg::UpdateVolleyChance(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.odds.volley_chance = val;
+                }
+            }
+            Msg::UpdateFrontage(val) => {
+                // Left empty or at zero, everyone fights.
+                self.odds.frontage = val.parse::<usize>().ok().filter(|frontage| *frontage > 0);
+            }
+            Msg::ToggleInProgress => {
+                self.in_progress = !self.in_progress;
+            }
+            Msg::UpdateRetreatKind(side, val) => {
+                let value = match self.odds.get_retreat(side) {
+                    Some(RetreatThreshold::Soldiers(soldiers)) => soldiers as f64,
+                    Some(RetreatThreshold::Percent(percent)) => percent,
+                    None => 25.,
+                };
+                let threshold = match val.as_str() {
+                    "soldiers" => Some(RetreatThreshold::Soldiers(value as usize)),
+                    "percent" => Some(RetreatThreshold::Percent(value)),
+                    _ => None,
+                };
+                self.odds.set_retreat(side, threshold);
+            }
+            Msg::UpdateRetreatValue(side, val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    let threshold = match self.odds.get_retreat(side) {
+                        Some(RetreatThreshold::Soldiers(_)) => {
+                            Some(RetreatThreshold::Soldiers(val as usize))
+                        }
+                        Some(RetreatThreshold::Percent(_)) => Some(RetreatThreshold::Percent(val)),
+                        None => None,
+                    };
+                    self.odds.set_retreat(side, threshold);
+                }
+            }
+            Msg::UpdateScoutingKind(side, val) => {
+                self.get_scouting_mut(side).kind = match val.as_str() {
+                    "range" => ScoutingKind::Range,
+                    "triangular" => ScoutingKind::Triangular,
+                    "weighted" => ScoutingKind::Weighted,
+                    _ => ScoutingKind::Exact,
+                };
+            }
+            Msg::UpdateScoutingMin(side, val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.get_scouting_mut(side).min = val;
+                }
+            }
+            Msg::UpdateScoutingMode(side, val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.get_scouting_mut(side).mode = val;
+                }
+            }
+            Msg::UpdateScoutingMax(side, val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.get_scouting_mut(side).max = val;
+                }
+            }
+            Msg::UpdateScoutingWeights(side, val) => {
+                self.get_scouting_mut(side).weights = val;
+            }
+            Msg::UpdateRoundsElapsed(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.rounds_elapsed = val;
+                }
+            }
+            Msg::UpdateStartedWith(side, val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    match side {
+                        Side::Attacker => self.started_with.0 = val,
+                        Side::Defender => self.started_with.1 = val,
+                    }
+                }
+            }
+            Msg::UpdateReinforcementSide(val) => {
+                self.reinforcement.side = match val.as_str() {
+                    "attacker" => Side::Attacker,
+                    _ => Side::Defender,
+                };
+            }
+            Msg::UpdateReinforcementSoldiers(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.reinforcement.soldiers = val;
+                }
+            }
+            Msg::UpdateReinforcementRound(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.reinforcement.round = val;
+                }
+            }
+            Msg::AddReinforcement => {
+                self.odds.reinforcements.push(self.reinforcement);
+            }
+            Msg::RemoveReinforcement(index) => {
+                if index < self.odds.reinforcements.len() {
+                    self.odds.reinforcements.remove(index);
+                }
+            }
+            Msg::UpdatePruneEpsilon(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.odds.prune_epsilon = val;
+                }
+            }
+            Msg::UpdateKillTolerance(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.odds.kill_tolerance = val;
+                }
+            }
+            Msg::UpdateResolveTolerance(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.resolve_tolerance = val;
+                }
+            }
+            Msg::ToggleRunUntilResolved => {
+                self.run_until_resolved = !self.run_until_resolved;
+            }
+            Msg::ToggleSolveUnlimited => {
+                self.solve_unlimited = !self.solve_unlimited;
+            }
+            Msg::UpdateResolutionOrder(val) => {
+                self.odds.resolution_order = match val.as_str() {
+                    "defender_first" => ResolutionOrder::DefenderFirst,
+                    "simultaneous" => ResolutionOrder::Simultaneous,
+                    _ => ResolutionOrder::AttackerFirst,
+                };
+            }
+            Msg::UpdateToggleChance(toggle, val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.fog.set_chance(&mut self.odds, toggle, val / 100.);
+                }
+            }
             Msg::ToggleAttackerPresent => {
-                self.odds.attacker_present = !self.odds.attacker_present;
+                self.odds.toggle(Toggle::AttackerPresent);
+                self.fog.clear(Toggle::AttackerPresent);
             }
             Msg::ToggleDefenderPresent => {
-                self.odds.defender_present = !self.odds.defender_present;
+                self.odds.toggle(Toggle::DefenderPresent);
+                self.fog.clear(Toggle::DefenderPresent);
             }
             Msg::ToggleAttackerBlessed => {
-                self.odds.attacker_blessed = !self.odds.attacker_blessed;
+                self.odds.toggle(Toggle::AttackerBlessed);
+                self.fog.clear(Toggle::AttackerBlessed);
             }
             Msg::ToggleDefenderBlessed => {
-                self.odds.defender_blessed = !self.odds.defender_blessed;
+                self.odds.toggle(Toggle::DefenderBlessed);
+                self.fog.clear(Toggle::DefenderBlessed);
             }
             Msg::ToggleAttackerClaimed => {
-                self.odds.attacker_claimed = !self.odds.attacker_claimed;
+                self.odds.toggle(Toggle::AttackerClaimed);
+                self.fog.clear(Toggle::AttackerClaimed);
             }
             Msg::ToggleDefenderClaimed => {
-                self.odds.defender_claimed = !self.odds.defender_claimed;
+                self.odds.toggle(Toggle::DefenderClaimed);
+                self.fog.clear(Toggle::DefenderClaimed);
             }
             Msg::ToggleDefenderFortified => {
-                self.odds.defender_fortified = !self.odds.defender_fortified;
+                self.odds.toggle(Toggle::DefenderFortified);
+                self.fog.clear(Toggle::DefenderFortified);
             }
             Msg::ToggleAttackerCity => {
-                self.odds.attacker_city = !self.odds.attacker_city;
+                self.odds.toggle(Toggle::AttackerCity);
+                self.fog.clear(Toggle::AttackerCity);
             }
             Msg::ToggleDefenderCity => {
-                self.odds.defender_city = !self.odds.defender_city;
+                self.odds.toggle(Toggle::DefenderCity);
+                self.fog.clear(Toggle::DefenderCity);
             }
             Msg::ToggleAttackerArchers => {
-                self.odds.attacker_archers = !self.odds.attacker_archers;
-                if self.odds.attacker_archers {
-                    self.odds.attacker_elites = false;
-                }
+                self.odds.toggle(Toggle::AttackerArchers);
+                self.fog.clear(Toggle::AttackerArchers);
             }
             Msg::ToggleDefenderArchers => {
-                self.odds.defender_archers = !self.odds.defender_archers;
-                if self.odds.defender_archers {
-                    self.odds.defender_elites = false;
-                }
+                self.odds.toggle(Toggle::DefenderArchers);
+                self.fog.clear(Toggle::DefenderArchers);
             }
             Msg::ToggleAttackerElites => {
-                self.odds.attacker_elites = !self.odds.attacker_elites;
-                if self.odds.attacker_elites {
-                    self.odds.attacker_archers = false;
-                }
+                self.odds.toggle(Toggle::AttackerElites);
+                self.fog.clear(Toggle::AttackerElites);
             }
             Msg::ToggleDefenderElites => {
-                self.odds.defender_elites = !self.odds.defender_elites;
-                if self.odds.defender_elites {
-                    self.odds.defender_archers = false;
+                self.odds.toggle(Toggle::DefenderElites);
+                self.fog.clear(Toggle::DefenderElites);
+            }
+            Msg::UpdateForceSide(val) => {
+                self.force_target.side = match val.as_str() {
+                    "defender" => Side::Defender,
+                    _ => Side::Attacker,
+                };
+            }
+            Msg::UpdateForceWinProbability(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.force_target.win_probability = val / 100.;
                 }
             }
-            Msg::Calculate => {
-                self.weights = Some(calculate_weights(
+            Msg::UpdateForceMinSurvivors(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.force_target.min_survivors = val;
+                }
+            }
+            Msg::UpdateForceMaxSoldiers(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.force_target.max_soldiers = val;
+                }
+            }
+            Msg::FindMinimumForce => {
+                self.force_target.opposing_soldiers = match self.force_target.side {
+                    Side::Attacker => self.starting_defenders as usize,
+                    Side::Defender => self.starting_attackers as usize,
+                };
+                self.force_result = Some(find_minimum_force(&self.force_target, &self.odds));
+            }
+            Msg::AddCampaignLeg => {
+                self.campaign_legs.push(CampaignLeg {
+                    defenders: self.starting_defenders as usize,
+                    odds: self.odds.clone(),
+                });
+                self.campaign_report = None;
+            }
+            Msg::RemoveCampaignLeg(index) => {
+                if index < self.campaign_legs.len() {
+                    self.campaign_legs.remove(index);
+                }
+                self.campaign_report = None;
+            }
+            Msg::RunCampaign => {
+                let campaign = Campaign {
+                    attackers: self
+                        .attacker_scouting
+                        .get_army_size(self.starting_attackers),
+                    legs: self.campaign_legs.clone(),
+                };
+                self.campaign_report = Some(run_campaign(&campaign));
+            }
+            Msg::UpdateObservations(val) => {
+                self.observations = val;
+            }
+            Msg::InferModifiers => {
+                // Counts are written as attackers/defenders, one pair per
+                // round starting with the counts before the first.
+                let observed: Vec<(usize, usize)> = self
+                    .observations
+                    .split(',')
+                    .filter_map(|entry| {
+                        let (attackers, defenders) = entry.split_once('/')?;
+                        Some((
+                            attackers.trim().parse().ok()?,
+                            defenders.trim().parse().ok()?,
+                        ))
+                    })
+                    .collect();
+                if let Some((attackers, defenders)) = observed.last().copied() {
+                    let posterior = infer_modifiers(&self.fog, &self.odds, &observed);
+                    self.prediction = posterior.as_ref().and_then(|posterior| {
+                        posterior
+                            .predict(attackers, defenders, observed.len() - 1)
+                            .pop()
+                    });
+                    self.posterior = Some(posterior);
+                }
+            }
+            Msg::AnalyzeSensitivity => {
+                self.sensitivities = Some(analyze_sensitivity(
+                    self.starting_attackers,
+                    self.starting_defenders,
+                    &self.odds,
+                ));
+            }
+            Msg::UpdatePolicySide(val) => {
+                self.policy_side = match val.as_str() {
+                    "defender" => Side::Defender,
+                    _ => Side::Attacker,
+                };
+            }
+            Msg::UpdatePolicyObjective(val) => {
+                self.policy_objective = match val.as_str() {
+                    "net_kills" => PolicyObjective::NetKills,
+                    _ => PolicyObjective::WinProbability { loss_penalty: 0.5 },
+                };
+            }
+            Msg::UpdatePolicyLossPenalty(val) => {
+                if let Ok(val) = val.parse::<f64>() {
+                    self.policy_objective = PolicyObjective::WinProbability { loss_penalty: val };
+                }
+            }
+            Msg::UpdatePolicyRound(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.policy_round = val;
+                }
+            }
+            Msg::SolvePolicy => {
+                self.policy = Some(solve_retreat_policy(
+                    self.starting_attackers as usize,
+                    self.starting_defenders as usize,
+                    self.policy_side,
+                    self.policy_objective,
+                    &self.odds,
+                ));
+            }
+            Msg::UpdateComposition(side, unit, val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    match side {
+                        Side::Attacker => self.mixed_attackers.set(unit, val),
+                        Side::Defender => self.mixed_defenders.set(unit, val),
+                    }
+                }
+            }
+            Msg::UpdateTargeting(side, slot, val) => {
+                if let Some(unit) = UnitType::ALL.into_iter().find(|unit| unit.name() == val) {
+                    // Swapping keeps every unit type in the order exactly once.
+                    let mut targeting = self.odds.get_targeting(side);
+                    if let Some(other) = targeting.iter().position(|target| *target == unit) {
+                        targeting.swap(slot, other);
+                    }
+                    self.odds.set_targeting(side, targeting);
+                }
+            }
+            Msg::RunMixedBattle => {
+                self.mixed_outcome = Some(run_mixed_battle(
+                    self.mixed_attackers,
+                    self.mixed_defenders,
+                    &self.odds,
+                ));
+            }
+            Msg::UpdateSweepAttackersMin(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_attackers.min = val;
+                }
+            }
+            Msg::UpdateSweepAttackersMax(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_attackers.max = val;
+                }
+            }
+            Msg::UpdateSweepAttackersStep(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_attackers.step = val;
+                }
+            }
+            Msg::UpdateSweepDefendersMin(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_defenders.min = val;
+                }
+            }
+            Msg::UpdateSweepDefendersMax(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_defenders.max = val;
+                }
+            }
+            Msg::UpdateSweepDefendersStep(val) => {
+                if let Ok(val) = val.parse::<usize>() {
+                    self.sweep_defenders.step = val;
+                }
+            }
+            Msg::RunSweep => {
+                self.sweep = Some(sweep_matchups(
+                    self.sweep_attackers,
+                    self.sweep_defenders,
+                    &self.odds,
+                ));
+            }
+            Msg::Simulate => {
+                self.simulation_seed += 1;
+                self.simulation = Some(simulate_battles(
                     self.starting_attackers,
                     self.starting_defenders,
                     &self.odds,
+                    10000,
+                    self.simulation_seed,
                 ));
             }
+            Msg::Calculate => {
+                let attackers = self
+                    .attacker_scouting
+                    .get_army_size(self.starting_attackers);
+                let defenders = self
+                    .defender_scouting
+                    .get_army_size(self.starting_defenders);
+                let starting = WarWeights::from_sizes(&attackers, &defenders);
+                let (rounds_elapsed, started_with) = if self.in_progress {
+                    (self.rounds_elapsed, Some(self.started_with))
+                } else {
+                    (0, None)
+                };
+                // Every combination of the modifiers nobody is sure about, each
+                // run on its own and blended by how likely it is.
+                let scenarios = self.fog.get_scenarios(&self.odds);
+                let histories: Vec<(Vec<WarWeights>, f64)> = scenarios
+                    .iter()
+                    .map(|(odds, weight)| {
+                        let history = if self.run_until_resolved {
+                            run_until_resolved(
+                                starting.clone(),
+                                rounds_elapsed,
+                                started_with,
+                                odds,
+                                self.resolve_tolerance,
+                            )
+                        } else {
+                            resume_battle(starting.clone(), rounds_elapsed, started_with, odds)
+                        };
+                        (history, *weight)
+                    })
+                    .collect();
+                let mut history = blend_histories(&histories);
+                self.history = Some(summarize_history(&history));
+                self.weights = history.pop();
+                self.scenario_count = scenarios.len();
+                // The same battle at the expected sizes, to show how much the
+                // scouting uncertainty widens the outcome.
+                self.exact_weights = if attackers.is_exact() && defenders.is_exact() {
+                    None
+                } else {
+                    let exact = WarWeights::starting(
+                        attackers.get_expected().round() as usize,
+                        defenders.get_expected().round() as usize,
+                    );
+                    let histories: Vec<(Vec<WarWeights>, f64)> = scenarios
+                        .iter()
+                        .map(|(odds, weight)| {
+                            (
+                                resume_battle(exact.clone(), rounds_elapsed, started_with, odds),
+                                *weight,
+                            )
+                        })
+                        .collect();
+                    blend_histories(&histories).pop()
+                };
+                self.unlimited_outcome = if self.solve_unlimited {
+                    let mut outcome = WarWeights::new(0, 0);
+                    for (odds, weight) in &scenarios {
+                        outcome.add_weighted(
+                            &solve_final_outcome_from(
+                                &starting,
+                                rounds_elapsed,
+                                started_with,
+                                odds,
+                            ),
+                            *weight,
+                        );
+                    }
+                    Some(outcome)
+                } else {
+                    None
+                };
+            }
         }
         true
     }
 }
 
 impl WarModel {
+    fn get_scouting_mut(&mut self, side: Side) -> &mut Scouting {
+        match side {
+            Side::Attacker => &mut self.attacker_scouting,
+            Side::Defender => &mut self.defender_scouting,
+        }
+    }
     fn get_results_table_node(
         &self,
-        ctx: &Context<WarModel>,
-        results: [f64; MAX_SOLDIERS + 1],
+        _ctx: &Context<WarModel>,
+        stats: OutcomeStats,
+        exact_stats: Option<OutcomeStats>,
     ) -> yew::virtual_dom::VNode {
-        let mut minimum = results
-            .iter()
-            .enumerate()
-            .find(|(_, r)| **r > 0.01)
-            .map(|(i, _)| i)
-            .unwrap_or(0);
-        let mut maximum = results
-            .iter()
-            .enumerate()
-            .rev()
-            .find(|(_, r)| **r > 0.01)
-            .map(|(i, _)| i)
-            .unwrap_or(0);
-        let average = results
-            .iter()
-            .enumerate()
-            .map(|(i, r)| i as f64 * r)
-            .sum::<f64>()
-            / results.iter().sum::<f64>();
-        let total_chance = results.iter().sum::<f64>();
-        let median = results
-            .iter()
-            .enumerate()
-            .map(|(i, r)| {
-                results
-                    .iter()
-                    .enumerate()
-                    .filter(|(j, _)| *j <= i)
-                    .map(|(_, r)| r)
-                    .sum::<f64>()
-            })
-            .enumerate()
-            .find(|(_, r)| *r >= total_chance / 2.)
-            .map(|(i, _)| i)
-            .unwrap_or(0) as f64;
-        let probable_result = results
-            .iter()
-            .enumerate()
-            .max_by_key(|(_, r)| (**r * 10000.) as usize)
-            .map(|(i, _)| i)
-            .unwrap_or(0);
-        if minimum == 0 && maximum == 0 {
-            if results[probable_result] < 0.0001 {
+        let results = stats.get_results();
+        let total_chance = stats.get_win_probability();
+        let (Some(probable_result), Some(average), Some(median)) =
+            (stats.get_mode(), stats.get_mean(), stats.get_median())
+        else {
+            return html!(<div class="no_results">{"No victory possible"}</div>);
+        };
+        let (minimum, maximum) = match stats.get_range_above(0.01) {
+            Some(range) => range,
+            None if results[probable_result] < 0.0001 => {
                 return html!(<div class="no_results">{"No victory possible"}</div>);
             }
-            minimum = probable_result.max(10) - 10;
-            maximum = (probable_result + 10).min(MAX_SOLDIERS);
-        }
+            None => (
+                probable_result.max(10) - 10,
+                (probable_result + 10).min(results.len() - 1),
+            ),
+        };
+        let deviation = stats.get_standard_deviation().unwrap_or(0.);
+        let (low, high) = stats.get_credible_interval(0.9).unwrap_or((median, median));
+        // Marks the cells to highlight directly, so the stylesheet does not
+        // need a rule for every possible survivor count.
+        let get_classes = |i: usize, header: bool| {
+            let mut classes = Vec::new();
+            if header && i == median {
+                classes.push("median");
+            }
+            if !header && i == probable_result {
+                classes.push("probable");
+            }
+            if !header && i == average.round() as usize {
+                classes.push("average");
+            }
+            classes.join(" ")
+        };
         html!(
-            <table class={format!("probable_{} average_{:.0} median_{:0}", probable_result, average.round(), median.round())}>
-                <thead>
-                    <tr>
-                      <th class="total">{"Total"}</th>
-                        {for (minimum..=maximum).map(|i| {
-                            let r = results[i];
-                            html!(<th class={format!("result_{} odds_{:.0}", i, r * 10000.)}>{i}</th>)
-                        })}
-                    </tr>
-                </thead>
-                <tbody>
-                    <tr>
-                        <td class="total">{format!("{:.2}%", total_chance * 100.0)}</td>
-                        {for (minimum..=maximum).map(|i| {
-                            let r = results[i];
-                            html!(<td class={format!("result_{} odds_{:.0}", i, r * 10000.)}>{format!("{:.2}%", r * 100.0)}</td>)
-                        })}
-                    </tr>
-                </tbody>
-            </table>
+            <>
+                <table>
+                    <thead>
+                        <tr>
+                          <th class="total">{"Total"}</th>
+                            {for (minimum..=maximum).map(|i| {
+                                let r = results[i];
+                                html!(<th class={format!("odds_{:.0} {}", r * 10000., get_classes(i, true))}>{i}</th>)
+                            })}
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <tr>
+                            <td class="total">{format!("{:.2}%", total_chance * 100.0)}</td>
+                            {for (minimum..=maximum).map(|i| {
+                                let r = results[i];
+                                html!(<td class={format!("odds_{:.0} {}", r * 10000., get_classes(i, false))}>{format!("{:.2}%", r * 100.0)}</td>)
+                            })}
+                        </tr>
+                    </tbody>
+                </table>
+                <div class="stats">{ format!("Mean {:.1} ± {:.1}, 90% interval {}–{}", average, deviation, low, high) }</div>
+                if let Some(exact_stats) = exact_stats {
+                    if let (Some(exact_average), Some(exact_deviation), Some((exact_low, exact_high))) =
+                        (exact_stats.get_mean(), exact_stats.get_standard_deviation(), exact_stats.get_credible_interval(0.9))
+                    {
+                        <div class="stats exact_stats">{ format!("At expected sizes: mean {:.1} ± {:.1}, 90% interval {}–{}", exact_average, exact_deviation, exact_low, exact_high) }</div>
+                    }
+                }
+            </>
         )
     }
     fn get_results_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
         if let Some(weights) = &self.weights {
-            let attacker_results = weights.get_attackers_winning_results();
-            let defender_results = weights.get_defenders_winning_results();
             html!(
                 <div id="results">
                     <div id="attacker_results">
                         <h2>{ "Attacker Results" }</h2>
-                        {self.get_results_table_node(ctx, attacker_results)}
+                        {self.get_results_table_node(ctx, weights.get_attacker_stats(), self.exact_weights.as_ref().map(|exact| exact.get_attacker_stats()))}
                     </div>
                     <div id="incomplete">
-                        <h2>{ format!("No win ({} rounds)", self.odds.round_count) }</h2>
+                        <h2>{ format!("No win ({} rounds)", self.get_round_count()) }</h2>
                         <span>{ format!("{:.2}%", weights.get_odds_of_no_win() * 100.0) }</span>
+                        if let Some(outcome) = &self.unlimited_outcome {
+                            <div id="unlimited_outcome">
+                                <h3>{ "No round limit" }</h3>
+                                <div>{ format!("Attacker win: {:.2}%", outcome.get_odds_of_attacker_win() * 100.0) }</div>
+                                <div>{ format!("Defender win: {:.2}%", outcome.get_odds_of_defender_win() * 100.0) }</div>
+                                if outcome.get_odds_of_mutual_destruction() > 0. {
+                                    <div>{ format!("Mutual destruction: {:.2}%", outcome.get_odds_of_mutual_destruction() * 100.0) }</div>
+                                }
+                                {self.get_retreats_node(outcome)}
+                                if outcome.get_odds_of_no_win() > 0. {
+                                    <div>{ format!("Never ends: {:.2}%", outcome.get_odds_of_no_win() * 100.0) }</div>
+                                }
+                            </div>
+                        }
+                        if weights.get_odds_of_mutual_destruction() > 0. {
+                            <div>{ format!("Mutual destruction: {:.2}%", weights.get_odds_of_mutual_destruction() * 100.0) }</div>
+                        }
+                        {self.get_retreats_node(weights)}
+                        if self.scenario_count > 1 {
+                            <div class="scenarios">{ format!("Blended over {} modifier scenarios", self.scenario_count) }</div>
+                        }
+                        <div class="accuracy">{ format!("Discarded: {:.6}%", weights.get_discarded_mass() * 100.0) }</div>
+                        if let Some(history) = &self.history {
+                            {self.get_ending_rounds_node(&EndingRounds::from_history(history))}
+                        }
                     </div>
                     <div id="defender_results">
                         <h2>{ "Defender Results" }</h2>
-                        {self.get_results_table_node(ctx, defender_results)}
+                        {self.get_results_table_node(ctx, weights.get_defender_stats(), self.exact_weights.as_ref().map(|exact| exact.get_defender_stats()))}
                     </div>
+                    {self.get_simulation_node(ctx)}
+                    if let Some(history) = &self.history {
+                        <div id="history">
+                            <h2>{ "Progress by Round" }</h2>
+                            {get_history_chart_node(history)}
+                        </div>
+                    }
                 </div>
             )
         } else {
             html!()
         }
     }
+    fn get_round_count(&self) -> usize {
+        match &self.history {
+            Some(history) => history.len() - 1,
+            None => self.odds.round_count,
+        }
+    }
+    fn get_ending_rounds_node(&self, ending_rounds: &EndingRounds) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="ending_rounds">
+                if let Some(expected) = ending_rounds.get_expected_round() {
+                    <div>{ format!("Expected end: round {:.1}", expected) }</div>
+                }
+                if let Some(median) = ending_rounds.get_median_round() {
+                    <div>{ format!("Median end: round {}", median) }</div>
+                }
+                {get_ending_rounds_chart_node(ending_rounds)}
+            </div>
+        )
+    }
+    // The simulator plays out fresh battles of known sizes and modifiers for
+    // `round_count` rounds, so it can only check results calculated the same
+    // way.
+    fn get_simulation_blocker(&self) -> Option<&'static str> {
+        let exact = self
+            .attacker_scouting
+            .get_army_size(self.starting_attackers)
+            .is_exact()
+            && self
+                .defender_scouting
+                .get_army_size(self.starting_defenders)
+                .is_exact();
+        if self.fog.is_uncertain() {
+            Some("Simulation needs every modifier to be certain.")
+        } else if !exact {
+            Some("Simulation needs exact army sizes.")
+        } else if self.in_progress {
+            Some("Simulation only plays battles from the start.")
+        } else if self.run_until_resolved {
+            Some("Simulation stops at the round limit.")
+        } else {
+            None
+        }
+    }
+    fn get_simulation_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        let format_estimate = |label: &str, estimate: Estimate| {
+            html!(
+                <div>{ format!("{}: {:.2}% ({:.2}%–{:.2}%)", label, estimate.value * 100., estimate.low * 100., estimate.high * 100.) }</div>
+            )
+        };
+        let blocker = self.get_simulation_blocker();
+        html!(
+            <div id="simulation">
+                <button disabled={ blocker.is_some() } onclick={ ctx.link().callback(|_| Msg::Simulate) }>{ "Cross-check with 10,000 simulated battles" }</button>
+                if let Some(blocker) = blocker {
+                    <div>{ blocker }</div>
+                }
+                if let Some(report) = &self.simulation {
+                    {format_estimate("Simulated attacker win", report.get_odds_of_attacker_win())}
+                    {format_estimate("Simulated defender win", report.get_odds_of_defender_win())}
+                    if report.attacker_retreats > 0 {
+                        {format_estimate("Simulated attacker retreat", report.get_odds_of_retreat(Side::Attacker))}
+                    }
+                    if report.defender_retreats > 0 {
+                        {format_estimate("Simulated defender retreat", report.get_odds_of_retreat(Side::Defender))}
+                    }
+                    {format_estimate("Simulated no win", report.get_odds_of_no_win())}
+                }
+            </div>
+        )
+    }
+    fn get_campaign_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="campaign">
+                <h2>{ "Campaign" }</h2>
+                <div>{ "Each leg takes the current defenders and modifiers. The attackers who survive one leg fight the next." }</div>
+                <ol>
+                    {for self.campaign_legs.iter().enumerate().map(|(index, leg)| {
+                        let modifiers: Vec<&str> = Toggle::ALL
+                            .iter()
+                            .filter(|toggle| leg.odds.get_toggle(**toggle))
+                            .map(|toggle| toggle.name())
+                            .collect();
+                        let report = self.campaign_report.as_ref().and_then(|report| report.legs.get(index));
+                        html!(
+                            <li>
+                                { format!("{} defenders", leg.defenders) }
+                                if !modifiers.is_empty() {
+                                    { format!(" ({})", modifiers.join(", ")) }
+                                }
+                                if let Some(stats) = report {
+                                    { format!(" — carried so far {:.2}%", stats.get_win_probability() * 100.) }
+                                }
+                                <button onclick={ ctx.link().callback(move |_| Msg::RemoveCampaignLeg(index)) }>{ "Remove" }</button>
+                            </li>
+                        )
+                    })}
+                </ol>
+                <button onclick={ ctx.link().callback(|_| Msg::AddCampaignLeg) }>{ "Add Leg" }</button>
+                <button onclick={ ctx.link().callback(|_| Msg::RunCampaign) }>{ "Run Campaign" }</button>
+                if let Some(report) = &self.campaign_report {
+                    <div>{ format!("Win the whole campaign: {:.2}%", report.get_odds_of_winning_campaign() * 100.) }</div>
+                    if let Some(stats) = report.get_final_stats() {
+                        if let (Some(mean), Some(deviation)) = (stats.get_mean(), stats.get_standard_deviation()) {
+                            <div>{ format!("Attackers left at the end: {:.1} ± {:.1}", mean, deviation) }</div>
+                        }
+                    }
+                }
+            </div>
+        )
+    }
+    fn get_inference_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="inference">
+                <h2>{ "Infer Hidden Modifiers" }</h2>
+                <div>
+                    <label for="observations">{ "Observed Attackers/Defenders by Round: " }</label>
+                    <input id="observations" type="text" value={ self.observations.clone() } oninput={ ctx.link().callback(|e| Msg::UpdateObservations(get_value_from_input_event(e))) } />
+                </div>
+                <button disabled={ !self.fog.is_consistent(&self.odds) } onclick={ ctx.link().callback(|_| Msg::InferModifiers) }>{ "Infer Modifiers" }</button>
+                {self.get_fog_warning_node()}
+                {match &self.posterior {
+                    Some(Some(posterior)) => html!(
+                        <>
+                            <table>
+                                <thead>
+                                    <tr>
+                                        <th>{ "Modifier" }</th>
+                                        <th>{ "Before" }</th>
+                                        <th>{ "After" }</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {for Toggle::ALL.iter().filter(|toggle| {
+                                        let prior = self.fog.get_chance(&self.odds, **toggle);
+                                        prior > 0. && prior < 1.
+                                    }).map(|toggle| html!(
+                                        <tr>
+                                            <td>{ toggle.name() }</td>
+                                            <td>{ format!("{:.1}%", self.fog.get_chance(&self.odds, *toggle) * 100.) }</td>
+                                            <td>{ format!("{:.1}%", posterior.get_chance(*toggle) * 100.) }</td>
+                                        </tr>
+                                    ))}
+                                </tbody>
+                            </table>
+                            if let Some(prediction) = &self.prediction {
+                                <div>{ format!("Predicted attacker win: {:.2}%", prediction.get_odds_of_attacker_win() * 100.) }</div>
+                                <div>{ format!("Predicted defender win: {:.2}%", prediction.get_odds_of_defender_win() * 100.) }</div>
+                                <div>{ format!("Predicted no win: {:.2}%", prediction.get_odds_of_no_win() * 100.) }</div>
+                            }
+                        </>
+                    ),
+                    Some(None) => html!(
+                        <div>{ "No combination of modifiers could produce those counts" }</div>
+                    ),
+                    None => html!(),
+                }}
+            </div>
+        )
+    }
+    fn get_solver_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        let (side_name, opposing_name) = match self.force_target.side {
+            Side::Attacker => ("attackers", "defenders"),
+            Side::Defender => ("defenders", "attackers"),
+        };
+        html!(
+            <div id="solver">
+                <h2>{ "Minimum Force" }</h2>
+                <div>
+                    <label for="force_side">{ "Solve For: " }</label>
+                    <select id="force_side" onchange={ ctx.link().callback(|e| Msg::UpdateForceSide(get_value_from_select_event(e))) }>
+                        <option value="attacker" selected={ self.force_target.side == Side::Attacker }>{ "Attackers" }</option>
+                        <option value="defender" selected={ self.force_target.side == Side::Defender }>{ "Defenders" }</option>
+                    </select>
+                </div>
+                <div>
+                    <label for="force_win_probability">{ "Target Win %: " }</label>
+                    <input id="force_win_probability" type="number" value={ (self.force_target.win_probability * 100.).to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateForceWinProbability(get_value_from_input_event(e))) } />
+                </div>
+                <div>
+                    <label for="force_min_survivors">{ "Minimum Survivors: " }</label>
+                    <input id="force_min_survivors" type="number" value={ self.force_target.min_survivors.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateForceMinSurvivors(get_value_from_input_event(e))) } />
+                </div>
+                <div>
+                    <label for="force_max_soldiers">{ "Search Up To: " }</label>
+                    <input id="force_max_soldiers" type="number" value={ self.force_target.max_soldiers.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateForceMaxSoldiers(get_value_from_input_event(e))) } />
+                </div>
+                <button onclick={ ctx.link().callback(|_| Msg::FindMinimumForce) }>{ "Find Minimum Force" }</button>
+                {match self.force_result {
+                    Some(Some((soldiers, chance))) => html!(
+                        <div class="solver_result">{ format!("{} {} win {:.2}% of the time against {} {}", soldiers, side_name, chance * 100., self.force_target.opposing_soldiers, opposing_name) }</div>
+                    ),
+                    Some(None) => html!(
+                        <div class="solver_result">{ format!("Not reachable with up to {} {}", self.force_target.max_soldiers, side_name) }</div>
+                    ),
+                    None => html!(),
+                }}
+            </div>
+        )
+    }
+    fn get_sensitivity_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="sensitivity">
+                <h2>{ "What Matters Most" }</h2>
+                <button onclick={ ctx.link().callback(|_| Msg::AnalyzeSensitivity) }>{ "Analyze Sensitivity" }</button>
+                if let Some(sensitivities) = &self.sensitivities {
+                    <table>
+                        <thead>
+                            <tr>
+                                <th>{ "Change" }</th>
+                                <th>{ "Attacker Win" }</th>
+                                <th>{ "Defender Win" }</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {for sensitivities.iter().filter(|sensitivity| sensitivity.get_impact() > 0.00005).map(|sensitivity| html!(
+                                <tr>
+                                    <td>{ sensitivity.adjustment.name() }</td>
+                                    <td>{ format!("{:+.2}%", sensitivity.attacker_change * 100.) }</td>
+                                    <td>{ format!("{:+.2}%", sensitivity.defender_change * 100.) }</td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                }
+            </div>
+        )
+    }
+    fn get_policy_panel_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="policy">
+                <h2>{ "When to Retreat" }</h2>
+                <div>
+                    <label for="policy_side">{ "Deciding Side: " }</label>
+                    <select id="policy_side" onchange={ ctx.link().callback(|e| Msg::UpdatePolicySide(get_value_from_select_event(e))) }>
+                        <option value="attacker" selected={ self.policy_side == Side::Attacker }>{ "Attackers" }</option>
+                        <option value="defender" selected={ self.policy_side == Side::Defender }>{ "Defenders" }</option>
+                    </select>
+                </div>
+                <div>
+                    <label for="policy_objective">{ "Objective: " }</label>
+                    <select id="policy_objective" onchange={ ctx.link().callback(|e| Msg::UpdatePolicyObjective(get_value_from_select_event(e))) }>
+                        <option value="win" selected={ matches!(self.policy_objective, PolicyObjective::WinProbability { .. }) }>{ "Win Chance Less Losses" }</option>
+                        <option value="net_kills" selected={ self.policy_objective == PolicyObjective::NetKills }>{ "Net Kills" }</option>
+                    </select>
+                </div>
+                if let PolicyObjective::WinProbability { loss_penalty } = self.policy_objective {
+                    <div>
+                        <label for="policy_loss_penalty">{ "Penalty for Losing Everyone: " }</label>
+                        <input id="policy_loss_penalty" type="number" value={ loss_penalty.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdatePolicyLossPenalty(get_value_from_input_event(e))) } />
+                    </div>
+                }
+                <div>
+                    <label for="policy_round">{ "Rounds Fought (volleys included): " }</label>
+                    <input id="policy_round" type="number" value={ self.policy_round.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdatePolicyRound(get_value_from_input_event(e))) } />
+                </div>
+                <button onclick={ ctx.link().callback(|_| Msg::SolvePolicy) }>{ "Solve Retreat Policy" }</button>
+                if let Some(policy) = &self.policy {
+                    <div>{ format!("Expected objective from {} attackers against {} defenders: {:.3}", policy.starting_attackers, policy.starting_defenders, policy.get_value(policy.starting_attackers, policy.starting_defenders, 0).unwrap_or_default()) }</div>
+                    {get_policy_node(policy, self.policy_round)}
+                }
+            </div>
+        )
+    }
+    fn get_sweep_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="sweep">
+                <h2>{ "Matchup Heatmap" }</h2>
+                <div class="sweep_ranges">
+                    <div>
+                        <label for="sweep_attackers_min">{ "Attackers From: " }</label>
+                        <input id="sweep_attackers_min" type="number" value={ self.sweep_attackers.min.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepAttackersMin(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="sweep_attackers_max">{ "To: " }</label>
+                        <input id="sweep_attackers_max" type="number" value={ self.sweep_attackers.max.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepAttackersMax(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="sweep_attackers_step">{ "Step: " }</label>
+                        <input id="sweep_attackers_step" type="number" value={ self.sweep_attackers.step.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepAttackersStep(get_value_from_input_event(e))) } />
+                    </div>
+                </div>
+                <div class="sweep_ranges">
+                    <div>
+                        <label for="sweep_defenders_min">{ "Defenders From: " }</label>
+                        <input id="sweep_defenders_min" type="number" value={ self.sweep_defenders.min.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepDefendersMin(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="sweep_defenders_max">{ "To: " }</label>
+                        <input id="sweep_defenders_max" type="number" value={ self.sweep_defenders.max.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepDefendersMax(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="sweep_defenders_step">{ "Step: " }</label>
+                        <input id="sweep_defenders_step" type="number" value={ self.sweep_defenders.step.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateSweepDefendersStep(get_value_from_input_event(e))) } />
+                    </div>
+                </div>
+                <button onclick={ ctx.link().callback(|_| Msg::RunSweep) }>{ "Run Sweep" }</button>
+                if let Some(sweep) = &self.sweep {
+                    {get_heatmap_node(sweep)}
+                }
+            </div>
+        )
+    }
+    // A side has either archers or elites, so their chances can't add up to
+    // more than 100%.
+    fn get_fog_warning_node(&self) -> yew::virtual_dom::VNode {
+        if self.fog.is_consistent(&self.odds) {
+            return html!();
+        }
+        html!(
+            <div class="fog_warning">{ "A side can't have both archers and elites: their chances add up to more than 100%." }</div>
+        )
+    }
+    fn get_chance_input_node(
+        &self,
+        ctx: &Context<WarModel>,
+        toggle: Toggle,
+    ) -> yew::virtual_dom::VNode {
+        let chance = self.fog.get_chance(&self.odds, toggle) * 100.;
+        html!(
+            <input class="toggle_chance" type="number" title="Chance %" value={ chance.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateToggleChance(toggle, get_value_from_input_event(e))) } />
+        )
+    }
+    fn get_composition_node(&self, ctx: &Context<WarModel>, side: Side) -> yew::virtual_dom::VNode {
+        let (title, composition) = match side {
+            Side::Attacker => ("Attackers", self.mixed_attackers),
+            Side::Defender => ("Defenders", self.mixed_defenders),
+        };
+        let targeting = self.odds.get_targeting(side);
+        html!(
+            <div class="composition">
+                <h3>{ title }</h3>
+                {for UnitType::ALL.into_iter().map(|unit| html!(
+                    <div>
+                        <label>{ format!("{}: ", unit.name()) }</label>
+                        <input type="number" value={ composition.get(unit).to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateComposition(side, unit, get_value_from_input_event(e))) } />
+                    </div>
+                ))}
+                <div>
+                    <label>{ "Targets: " }</label>
+                    {for targeting.into_iter().enumerate().map(|(slot, target)| html!(
+                        <select onchange={ ctx.link().callback(move |e| Msg::UpdateTargeting(side, slot, get_value_from_select_event(e))) }>
+                            {for UnitType::ALL.into_iter().map(|unit| html!(
+                                <option value={ unit.name() } selected={ unit == target }>{ unit.name() }</option>
+                            ))}
+                        </select>
+                    ))}
+                </div>
+            </div>
+        )
+    }
+
+    fn get_mixed_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="mixed">
+                <h2>{ "Mixed Armies" }</h2>
+                <div>{ "Each unit type fights with its own archer or elite modifiers. Each side picks off the other's units in its target order." }</div>
+                {self.get_composition_node(ctx, Side::Attacker)}
+                {self.get_composition_node(ctx, Side::Defender)}
+                <div>
+                    <button onclick={ ctx.link().callback(|_| Msg::RunMixedBattle) }>{ "Run Mixed Battle" }</button>
+                </div>
+                if let Some(outcome) = &self.mixed_outcome {
+                    {self.get_mixed_outcome_node(outcome)}
+                }
+            </div>
+        )
+    }
+
+    fn get_mixed_outcome_node(&self, outcome: &MixedWeights) -> yew::virtual_dom::VNode {
+        let totals = outcome.get_totals();
+        html!(
+            <div>
+                <div>{ format!("Attacker win: {:.2}%", totals.get_odds_of_attacker_win() * 100.) }</div>
+                <div>{ format!("Defender win: {:.2}%", totals.get_odds_of_defender_win() * 100.) }</div>
+                <div>{ format!("Still fighting: {:.2}%", totals.get_odds_of_no_win() * 100.) }</div>
+                <table>
+                    <tr>
+                        <th>{ "Expected Left" }</th>
+                        {for UnitType::ALL.into_iter().map(|unit| html!(<th>{ unit.name() }</th>))}
+                    </tr>
+                    {for [Side::Attacker, Side::Defender].into_iter().map(|side| html!(
+                        <tr>
+                            <td>{ if side == Side::Attacker { "Attackers" } else { "Defenders" } }</td>
+                            {for UnitType::ALL.into_iter().map(|unit| html!(
+                                <td>{ outcome.get_expected(side, unit).map_or("–".to_string(), |expected| format!("{:.1}", expected)) }</td>
+                            ))}
+                        </tr>
+                    ))}
+                </table>
+            </div>
+        )
+    }
+
+    fn get_reinforcements_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
+        html!(
+            <div id="reinforcements">
+                <h3>{ "Reinforcements" }</h3>
+                <ul>
+                    {for self.odds.reinforcements.iter().enumerate().map(|(index, reinforcement)| {
+                        let side_name = match reinforcement.side {
+                            Side::Attacker => "attackers",
+                            Side::Defender => "defenders",
+                        };
+                        html!(
+                            <li>
+                                { format!("+{} {} at round {} ", reinforcement.soldiers, side_name, reinforcement.round) }
+                                <button onclick={ ctx.link().callback(move |_| Msg::RemoveReinforcement(index)) }>{ "Remove" }</button>
+                            </li>
+                        )
+                    })}
+                </ul>
+                <div>
+                    <label for="reinforcement_soldiers">{ "+" }</label>
+                    <input id="reinforcement_soldiers" type="number" value={ self.reinforcement.soldiers.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateReinforcementSoldiers(get_value_from_input_event(e))) } />
+                    <select id="reinforcement_side" onchange={ ctx.link().callback(|e| Msg::UpdateReinforcementSide(get_value_from_select_event(e))) }>
+                        <option value="attacker" selected={ self.reinforcement.side == Side::Attacker }>{ "Attackers" }</option>
+                        <option value="defender" selected={ self.reinforcement.side == Side::Defender }>{ "Defenders" }</option>
+                    </select>
+                    <label for="reinforcement_round">{ " at Round: " }</label>
+                    <input id="reinforcement_round" type="number" value={ self.reinforcement.round.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateReinforcementRound(get_value_from_input_event(e))) } />
+                    <button onclick={ ctx.link().callback(|_| Msg::AddReinforcement) }>{ "Add" }</button>
+                </div>
+            </div>
+        )
+    }
+    fn get_retreat_node(&self, ctx: &Context<WarModel>, side: Side) -> yew::virtual_dom::VNode {
+        let prefix = match side {
+            Side::Attacker => "attacker",
+            Side::Defender => "defender",
+        };
+        let threshold = self.odds.get_retreat(side);
+        html!(
+            <div class="retreat">
+                <label for={ format!("{}_retreat", prefix) }>{ "Retreat: " }</label>
+                <select id={ format!("{}_retreat", prefix) } onchange={ ctx.link().callback(move |e| Msg::UpdateRetreatKind(side, get_value_from_select_event(e))) }>
+                    <option value="never" selected={ threshold.is_none() }>{ "Never" }</option>
+                    <option value="soldiers" selected={ matches!(threshold, Some(RetreatThreshold::Soldiers(_))) }>{ "Below Soldiers" }</option>
+                    <option value="percent" selected={ matches!(threshold, Some(RetreatThreshold::Percent(_))) }>{ "Below % of Start" }</option>
+                </select>
+                {match threshold {
+                    Some(RetreatThreshold::Soldiers(soldiers)) => html!(
+                        <input id={ format!("{}_retreat_value", prefix) } type="number" value={ soldiers.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateRetreatValue(side, get_value_from_input_event(e))) } />
+                    ),
+                    Some(RetreatThreshold::Percent(percent)) => html!(
+                        <input id={ format!("{}_retreat_value", prefix) } type="number" value={ percent.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateRetreatValue(side, get_value_from_input_event(e))) } />
+                    ),
+                    None => html!(),
+                }}
+            </div>
+        )
+    }
+    fn get_retreats_node(&self, weights: &WarWeights) -> yew::virtual_dom::VNode {
+        html!(
+            <>
+                {for [(Side::Attacker, "Attacker"), (Side::Defender, "Defender")].iter().filter(|(side, _)| weights.get_odds_of_retreat(*side) > 0.).map(|(side, name)| {
+                    let stats = OutcomeStats::from_results(weights.get_retreating_results(*side));
+                    html!(
+                        <div class="retreats">
+                            { format!("{} retreats: {:.2}%", name, stats.get_win_probability() * 100.) }
+                            if let Some(mean) = stats.get_mean() {
+                                { format!(", with {:.1} left on average", mean) }
+                            }
+                        </div>
+                    )
+                })}
+            </>
+        )
+    }
+    fn get_scouting_node(&self, ctx: &Context<WarModel>, side: Side) -> yew::virtual_dom::VNode {
+        let (scouting, prefix) = match side {
+            Side::Attacker => (&self.attacker_scouting, "attacker"),
+            Side::Defender => (&self.defender_scouting, "defender"),
+        };
+        html!(
+            <div class="scouting">
+                <div>
+                    <label for={ format!("{}_scouting", prefix) }>{ "Size Known: " }</label>
+                    <select id={ format!("{}_scouting", prefix) } onchange={ ctx.link().callback(move |e| Msg::UpdateScoutingKind(side, get_value_from_select_event(e))) }>
+                        <option value="exact" selected={ scouting.kind == ScoutingKind::Exact }>{ "Exactly" }</option>
+                        <option value="range" selected={ scouting.kind == ScoutingKind::Range }>{ "Within a Range" }</option>
+                        <option value="triangular" selected={ scouting.kind == ScoutingKind::Triangular }>{ "Roughly" }</option>
+                        <option value="weighted" selected={ scouting.kind == ScoutingKind::Weighted }>{ "By Weights" }</option>
+                    </select>
+                </div>
+                if matches!(scouting.kind, ScoutingKind::Range | ScoutingKind::Triangular) {
+                    <div>
+                        <label for={ format!("{}_scouting_min", prefix) }>{ "From: " }</label>
+                        <input id={ format!("{}_scouting_min", prefix) } type="number" value={ scouting.min.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateScoutingMin(side, get_value_from_input_event(e))) } />
+                    </div>
+                }
+                if scouting.kind == ScoutingKind::Triangular {
+                    <div>
+                        <label for={ format!("{}_scouting_mode", prefix) }>{ "Most Likely: " }</label>
+                        <input id={ format!("{}_scouting_mode", prefix) } type="number" value={ scouting.mode.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateScoutingMode(side, get_value_from_input_event(e))) } />
+                    </div>
+                }
+                if matches!(scouting.kind, ScoutingKind::Range | ScoutingKind::Triangular) {
+                    <div>
+                        <label for={ format!("{}_scouting_max", prefix) }>{ "To: " }</label>
+                        <input id={ format!("{}_scouting_max", prefix) } type="number" value={ scouting.max.to_string() } oninput={ ctx.link().callback(move |e| Msg::UpdateScoutingMax(side, get_value_from_input_event(e))) } />
+                    </div>
+                }
+                if scouting.kind == ScoutingKind::Weighted {
+                    <div>
+                        <label for={ format!("{}_scouting_weights", prefix) }>{ "Size:Weight, ...: " }</label>
+                        <input id={ format!("{}_scouting_weights", prefix) } type="text" value={ scouting.weights.clone() } oninput={ ctx.link().callback(move |e| Msg::UpdateScoutingWeights(side, get_value_from_input_event(e))) } />
+                    </div>
+                }
+            </div>
+        )
+    }
     fn get_settings_node(&self, ctx: &Context<WarModel>) -> yew::virtual_dom::VNode {
         let vnode = html! (
             <div id="odds_settings">
@@ -527,74 +1534,151 @@ impl WarModel {
                 </div>
                 <div id="attackers">
                     <div>
-                        <label for="starting_attackers">{ "Starting Attackers: " }</label>
+                        <label for="starting_attackers">{ if self.in_progress { "Attackers Left: " } else { "Starting Attackers: " } }</label>
                         <input id="starting_attackers" type="number" value={ self.starting_attackers.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateStartingAttackers(get_value_from_input_event(e))) } />
                     </div>
+                    {self.get_scouting_node(ctx, Side::Attacker)}
+                    {self.get_retreat_node(ctx, Side::Attacker)}
                     <div>
                         <label for="attacker_present">{ "Attacker Commander Present: " }</label>
                         <input id="attacker_present" type="checkbox" checked={ self.odds.attacker_present } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerPresent) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerPresent)}
                     </div>
                     <div>
                         <label for="attacker_blessed">{ "Attacker Blessed: " }</label>
                         <input id="attacker_blessed" type="checkbox" checked={ self.odds.attacker_blessed } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerBlessed) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerBlessed)}
                     </div>
                     <div>
                         <label for="attacker_claimed">{ "Attacker Claimed: " }</label>
                         <input id="attacker_claimed" type="checkbox" checked={ self.odds.attacker_claimed } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerClaimed) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerClaimed)}
                     </div>
                     <div>
                         <label for="attacker_city">{ "Attacker City: " }</label>
                         <input id="attacker_city" type="checkbox" checked={ self.odds.attacker_city } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerCity) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerCity)}
                     </div>
                     <div>
                         <label for="attacker_archers">{ "Attacker are Archers: " }</label>
                         <input id="attacker_archers" type="checkbox" checked={ self.odds.attacker_archers } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerArchers) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerArchers)}
                     </div>
                     <div>
                         <label for="attacker_elites">{ "Attacker are Elites: " }</label>
                         <input id="attacker_elites" type="checkbox" checked={ self.odds.attacker_elites } onclick={ ctx.link().callback(|_| Msg::ToggleAttackerElites) } />
+                        {self.get_chance_input_node(ctx, Toggle::AttackerElites)}
                     </div>
                 </div>
                 <div id="defenders">
                     <div>
-                        <label for="starting_defenders">{ "Starting Defenders: " }</label>
+                        <label for="starting_defenders">{ if self.in_progress { "Defenders Left: " } else { "Starting Defenders: " } }</label>
                         <input id="starting_defenders" type="number" value={ self.starting_defenders.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateStartingDefenders(get_value_from_input_event(e))) } />
                     </div>
+                    {self.get_scouting_node(ctx, Side::Defender)}
+                    {self.get_retreat_node(ctx, Side::Defender)}
                     <div>
                         <label for="defender_present">{ "Defender Commander Present: " }</label>
                         <input id="defender_present" type="checkbox" checked={ self.odds.defender_present } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderPresent) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderPresent)}
                     </div>
                     <div>
                         <label for="defender_blessed">{ "Defender Blessed: " }</label>
                         <input id="defender_blessed" type="checkbox" checked={ self.odds.defender_blessed } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderBlessed) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderBlessed)}
                     </div>
                     <div>
                         <label for="defender_claimed">{ "Defender Claimed: " }</label>
                         <input id="defender_claimed" type="checkbox" checked={ self.odds.defender_claimed } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderClaimed) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderClaimed)}
                     </div>
                     <div>
                         <label for="defender_fortified">{ "Defender Fortified: " }</label>
                         <input id="defender_fortified" type="checkbox" checked={ self.odds.defender_fortified } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderFortified) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderFortified)}
                     </div>
                     <div>
                         <label for="defender_city">{ "Defender City: " }</label>
                         <input id="defender_city" type="checkbox" checked={ self.odds.defender_city } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderCity) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderCity)}
                     </div>
                     <div>
                         <label for="defender_archers">{ "Defender Archers: " }</label>
                         <input id="defender_archers" type="checkbox" checked={ self.odds.defender_archers } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderArchers) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderArchers)}
                     </div>
                     <div>
                         <label for="defender_elites">{ "Defender Elites: " }</label>
                         <input id="defender_elites" type="checkbox" checked={ self.odds.defender_elites } onclick={ ctx.link().callback(|_| Msg::ToggleDefenderElites) } />
+                        {self.get_chance_input_node(ctx, Toggle::DefenderElites)}
                     </div>
                 </div>
+                <div id="in_progress">
+                    <div>
+                        <label for="in_progress">{ "Battle in Progress: " }</label>
+                        <input id="in_progress" type="checkbox" checked={ self.in_progress } onclick={ ctx.link().callback(|_| Msg::ToggleInProgress) } />
+                    </div>
+                    if self.in_progress {
+                        <div>
+                            <label for="rounds_elapsed">{ "Rounds Elapsed (volleys included): " }</label>
+                            <input id="rounds_elapsed" type="number" value={ self.rounds_elapsed.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateRoundsElapsed(get_value_from_input_event(e))) } />
+                        </div>
+                        <div>
+                            <label for="attackers_started_with">{ "Attackers at Start: " }</label>
+                            <input id="attackers_started_with" type="number" value={ self.started_with.0.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateStartedWith(Side::Attacker, get_value_from_input_event(e))) } />
+                            <label for="defenders_started_with">{ "Defenders at Start: " }</label>
+                            <input id="defenders_started_with" type="number" value={ self.started_with.1.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateStartedWith(Side::Defender, get_value_from_input_event(e))) } />
+                        </div>
+                    }
+                </div>
+                {self.get_reinforcements_node(ctx)}
                 <div id="calculate">
+                    <div>
+                        <label for="resolution_order">{ "Resolution Order: " }</label>
+                        <select id="resolution_order" onchange={ ctx.link().callback(|e| Msg::UpdateResolutionOrder(get_value_from_select_event(e))) }>
+                            <option value="attacker_first" selected={ self.odds.resolution_order == ResolutionOrder::AttackerFirst }>{ "Attacker First" }</option>
+                            <option value="defender_first" selected={ self.odds.resolution_order == ResolutionOrder::DefenderFirst }>{ "Defender First" }</option>
+                            <option value="simultaneous" selected={ self.odds.resolution_order == ResolutionOrder::Simultaneous }>{ "Simultaneous" }</option>
+                        </select>
+                    </div>
                     <div>
                         <label for="round_count">{ "Round Count: " }</label>
                         <input id="round_count" type="number" value={ self.odds.round_count.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateRoundCount(get_value_from_input_event(e))) } />
                     </div>
-                    <button onclick={ ctx.link().callback(|_| Msg::Calculate) }>{ "Calculate" }</button>
+                    <div>
+                        <label for="frontage">{ "Frontage (blank for open ground): " }</label>
+                        <input id="frontage" type="number" value={ self.odds.frontage.map(|frontage| frontage.to_string()).unwrap_or_default() } oninput={ ctx.link().callback(|e| Msg::UpdateFrontage(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="volley_rounds">{ "Archer Volley Rounds: " }</label>
+                        <input id="volley_rounds" type="number" value={ self.odds.volley_rounds.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateVolleyRounds(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="volley_chance">{ "Archer Volley Chance: " }</label>
+                        <input id="volley_chance" type="number" value={ self.odds.volley_chance.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateVolleyChance(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="run_until_resolved">{ "Run Until Resolved: " }</label>
+                        <input id="run_until_resolved" type="checkbox" checked={ self.run_until_resolved } onclick={ ctx.link().callback(|_| Msg::ToggleRunUntilResolved) } />
+                    </div>
+                    <div>
+                        <label for="resolve_tolerance">{ "Unresolved Tolerance: " }</label>
+                        <input id="resolve_tolerance" type="number" value={ self.resolve_tolerance.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateResolveTolerance(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="solve_unlimited">{ "Solve Without Round Limit: " }</label>
+                        <input id="solve_unlimited" type="checkbox" checked={ self.solve_unlimited } onclick={ ctx.link().callback(|_| Msg::ToggleSolveUnlimited) } />
+                    </div>
+                    <div>
+                        <label for="prune_epsilon">{ "Pruning Epsilon: " }</label>
+                        <input id="prune_epsilon" type="number" value={ self.odds.prune_epsilon.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdatePruneEpsilon(get_value_from_input_event(e))) } />
+                    </div>
+                    <div>
+                        <label for="kill_tolerance">{ "Kill Tail Tolerance: " }</label>
+                        <input id="kill_tolerance" type="number" value={ self.odds.kill_tolerance.to_string() } oninput={ ctx.link().callback(|e| Msg::UpdateKillTolerance(get_value_from_input_event(e))) } />
+                    </div>
+                    <button disabled={ !self.fog.is_consistent(&self.odds) } onclick={ ctx.link().callback(|_| Msg::Calculate) }>{ "Calculate" }</button>
+                    {self.get_fog_warning_node()}
                 </div>
             </div>
         );
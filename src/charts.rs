@@ -0,0 +1,138 @@
+use war_core::{EndingRounds, RetreatPolicy, RoundSummary, Sweep};
+use yew::prelude::*;
+
+const CHART_WIDTH: f64 = 400.;
+const CHART_HEIGHT: f64 = 150.;
+
+fn get_line_points(history: &[RoundSummary], value: impl Fn(&RoundSummary) -> f64) -> String {
+    let step = CHART_WIDTH / (history.len().max(2) - 1) as f64;
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, summary)| {
+            format!(
+                "{:.1},{:.1}",
+                i as f64 * step,
+                CHART_HEIGHT * (1. - value(summary))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn get_history_chart_node(history: &[RoundSummary]) -> Html {
+    let rounds = history.len().saturating_sub(1);
+    html!(
+        <div class="history_chart">
+            <svg viewBox={ format!("-30 -10 {} {}", CHART_WIDTH + 40., CHART_HEIGHT + 30.) }>
+                <line class="axis" x1="0" y1={ CHART_HEIGHT.to_string() } x2={ CHART_WIDTH.to_string() } y2={ CHART_HEIGHT.to_string() } />
+                <line class="axis" x1="0" y1="0" x2="0" y2={ CHART_HEIGHT.to_string() } />
+                <text class="axis_label" x="-4" y="4" text-anchor="end">{ "100%" }</text>
+                <text class="axis_label" x="-4" y={ CHART_HEIGHT.to_string() } text-anchor="end">{ "0%" }</text>
+                <text class="axis_label" x={ CHART_WIDTH.to_string() } y={ (CHART_HEIGHT + 14.).to_string() } text-anchor="end">{ format!("Round {}", rounds) }</text>
+                <polyline class="attacker_line" points={ get_line_points(history, |summary| summary.attackers_won) } />
+                <polyline class="defender_line" points={ get_line_points(history, |summary| summary.defenders_won) } />
+            </svg>
+            <div class="legend">
+                <span class="attacker_legend">{ "Attacker won by round" }</span>
+                <span class="defender_legend">{ "Defender won by round" }</span>
+            </div>
+        </div>
+    )
+}
+
+pub fn get_ending_rounds_chart_node(ending_rounds: &EndingRounds) -> Html {
+    let peak = ending_rounds
+        .endings
+        .iter()
+        .cloned()
+        .fold(0., f64::max)
+        .max(1e-9);
+    let bar_width = CHART_WIDTH / ending_rounds.endings.len().max(1) as f64;
+    html!(
+        <div class="ending_rounds_chart">
+            <svg viewBox={ format!("0 -10 {} {}", CHART_WIDTH, CHART_HEIGHT / 2. + 10.) }>
+                {for ending_rounds.attacker_wins.iter().zip(&ending_rounds.defender_wins).enumerate().map(|(round, (attacker, defender))| {
+                    let x = round as f64 * bar_width;
+                    let attacker_height = attacker / peak * CHART_HEIGHT / 2.;
+                    let defender_height = defender / peak * CHART_HEIGHT / 2.;
+                    html!(
+                        <g>
+                            <title>{ format!("Round {}: attacker {:.2}%, defender {:.2}%", round, attacker * 100., defender * 100.) }</title>
+                            <rect class="attacker_bar" x={ format!("{:.1}", x) } y={ format!("{:.1}", CHART_HEIGHT / 2. - attacker_height) } width={ format!("{:.1}", bar_width * 0.9) } height={ format!("{:.1}", attacker_height) } />
+                            <rect class="defender_bar" x={ format!("{:.1}", x) } y={ format!("{:.1}", CHART_HEIGHT / 2. - attacker_height - defender_height) } width={ format!("{:.1}", bar_width * 0.9) } height={ format!("{:.1}", defender_height) } />
+                        </g>
+                    )
+                })}
+            </svg>
+        </div>
+    )
+}
+
+const HEATMAP_CELL: f64 = 16.;
+
+// Red where the defenders hold, green where the attackers break through.
+fn get_heat_colour(chance: f64) -> String {
+    format!("hsl({:.0}, 70%, 45%)", chance.clamp(0., 1.) * 120.)
+}
+
+pub fn get_heatmap_node(sweep: &Sweep) -> Html {
+    let margin = 40.;
+    let width = sweep.attackers.len() as f64 * HEATMAP_CELL;
+    let height = sweep.defenders.len() as f64 * HEATMAP_CELL;
+    html!(
+        <div class="heatmap">
+            <svg viewBox={ format!("{} 0 {} {}", -margin, width + margin, height + margin) }>
+                {for sweep.defenders.iter().enumerate().map(|(row, defenders)| html!(
+                    <text class="axis_label" x="-4" y={ format!("{:.1}", (row as f64 + 0.7) * HEATMAP_CELL) } text-anchor="end">{ defenders }</text>
+                ))}
+                {for sweep.attackers.iter().enumerate().map(|(column, attackers)| html!(
+                    <text class="axis_label" x={ format!("{:.1}", (column as f64 + 0.5) * HEATMAP_CELL) } y={ format!("{:.1}", height + 12.) } text-anchor="middle">{ attackers }</text>
+                ))}
+                <text class="axis_label" x={ format!("{:.1}", width / 2.) } y={ format!("{:.1}", height + 28.) } text-anchor="middle">{ "Attackers" }</text>
+                {for sweep.attacker_wins.iter().enumerate().flat_map(|(row, chances)| {
+                    chances.iter().enumerate().map(move |(column, chance)| html!(
+                        <rect x={ format!("{:.1}", column as f64 * HEATMAP_CELL) } y={ format!("{:.1}", row as f64 * HEATMAP_CELL) } width={ HEATMAP_CELL.to_string() } height={ HEATMAP_CELL.to_string() } fill={ get_heat_colour(*chance) }>
+                            <title>{ format!("{} attackers vs {} defenders: {:.1}%", sweep.attackers[column], sweep.defenders[row], chance * 100.) }</title>
+                        </rect>
+                    ))
+                })}
+            </svg>
+        </div>
+    )
+}
+
+// Green where the deciding side should fight on, red where it should pull
+// out and grey where the battle is already over.
+pub fn get_policy_node(policy: &RetreatPolicy, round: usize) -> Html {
+    let cell = CHART_WIDTH / (policy.max_attackers.max(policy.max_defenders) + 1) as f64;
+    let width = (policy.max_attackers + 1) as f64 * cell;
+    let height = (policy.max_defenders + 1) as f64 * cell;
+    let margin = 30.;
+    html!(
+        <div class="policy_chart">
+            <svg viewBox={ format!("{} 0 {} {}", -margin, width + margin, height + margin) }>
+                <text class="axis_label" x="-4" y="8" text-anchor="end">{ "0" }</text>
+                <text class="axis_label" x="-4" y={ format!("{:.1}", height) } text-anchor="end">{ policy.max_defenders }</text>
+                <text class="axis_label" x={ format!("{:.1}", width) } y={ format!("{:.1}", height + 12.) } text-anchor="end">{ policy.max_attackers }</text>
+                <text class="axis_label" x={ format!("{:.1}", width / 2.) } y={ format!("{:.1}", height + 24.) } text-anchor="middle">{ "Attackers, with defenders down the side" }</text>
+                {for (0..=policy.max_attackers).flat_map(|attackers| {
+                    (0..=policy.max_defenders).map(move |defenders| {
+                        let class = if attackers == 0 || defenders == 0 {
+                            "finished"
+                        } else if policy.should_continue(attackers, defenders, round) == Some(true) {
+                            "fight_on"
+                        } else {
+                            "retreat"
+                        };
+                        html!(
+                            <rect class={ class } x={ format!("{:.2}", attackers as f64 * cell) } y={ format!("{:.2}", defenders as f64 * cell) } width={ format!("{:.2}", cell) } height={ format!("{:.2}", cell) }>
+                                <title>{ format!("{} attackers vs {} defenders: {} ({:.3})", attackers, defenders, class.replace('_', " "), policy.get_value(attackers, defenders, round).unwrap_or_default()) }</title>
+                            </rect>
+                        )
+                    })
+                })}
+            </svg>
+        </div>
+    )
+}
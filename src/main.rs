@@ -1,7 +1,7 @@
 use app::WarModel;
 
 mod app;
-mod calculate;
+mod charts;
 
 fn main() {
     yew::Renderer::<WarModel>::new().render();
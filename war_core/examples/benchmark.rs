@@ -0,0 +1,109 @@
+// Times the engine on a few battle sizes. Run with
+// `cargo run --release -p war_core --example benchmark`.
+use std::time::Instant;
+
+use war_core::{
+    calculate_mixture, calculate_weights, run_mixed_battle, sweep_matchups, ArmySize, Composition,
+    RetreatThreshold, SweepRange, WarOdds,
+};
+
+fn main() {
+    let exact = WarOdds {
+        kill_tolerance: 0.,
+        prune_epsilon: 0.,
+        ..WarOdds::default()
+    };
+    for (label, attackers, defenders, odds) in [
+        ("100v100", 100., 100., WarOdds::default()),
+        ("100v100 exact", 100., 100., exact.clone()),
+        ("300v300", 300., 300., WarOdds::default()),
+        ("1000v1000", 1000., 1000., WarOdds::default()),
+    ] {
+        let runs = 5;
+        let start = Instant::now();
+        let mut win = 0.;
+        for _ in 0..runs {
+            win = calculate_weights(attackers, defenders, &odds).get_odds_of_attacker_win();
+        }
+        println!(
+            "{:<14} {:>10.2?} per run (attacker win {:.6})",
+            label,
+            start.elapsed() / runs,
+            win
+        );
+    }
+
+    let volleys = WarOdds {
+        volley_rounds: 2,
+        ..WarOdds::default()
+    };
+    for (label, attackers, defenders) in [
+        (
+            "mixed 60v60",
+            Composition::new(40, 10, 10),
+            Composition::new(40, 10, 10),
+        ),
+        (
+            "mixed 100v100",
+            Composition::new(60, 20, 20),
+            Composition::new(70, 20, 10),
+        ),
+    ] {
+        let runs = 5;
+        let start = Instant::now();
+        let mut win = 0.;
+        for _ in 0..runs {
+            win = run_mixed_battle(attackers, defenders, &volleys)
+                .get_totals()
+                .get_odds_of_attacker_win();
+        }
+        println!(
+            "{:<14} {:>10.2?} per run (attacker win {:.6})",
+            label,
+            start.elapsed() / runs,
+            win
+        );
+    }
+
+    // The default matchup sweep in the app.
+    let range = SweepRange {
+        min: 20,
+        max: 200,
+        step: 20,
+    };
+    let start = Instant::now();
+    let sweep = sweep_matchups(range, range, &WarOdds::default());
+    println!(
+        "{:<14} {:>10.2?} per run (attacker win {:.6})",
+        "sweep 10x10",
+        start.elapsed(),
+        sweep.get(200, 200).unwrap()
+    );
+
+    // The app's default scouting ranges, with and without a percentage
+    // retreat. Starting sizes that share thresholds are fought together, so
+    // the retreat should cost a few times the plain mixture rather than one
+    // battle per pair of sizes.
+    let scouted = ArmySize::Uniform { min: 80, max: 120 };
+    let retreating = WarOdds {
+        attacker_retreat: Some(RetreatThreshold::Percent(25.)),
+        ..WarOdds::default()
+    };
+    let mut timings = Vec::new();
+    for (label, odds) in [("mixture", WarOdds::default()), ("mixture 25%", retreating)] {
+        let start = Instant::now();
+        let win = calculate_mixture(&scouted, &scouted, &odds).get_odds_of_attacker_win();
+        timings.push(start.elapsed());
+        println!(
+            "{:<14} {:>10.2?} per run (attacker win {:.6})",
+            label,
+            start.elapsed(),
+            win
+        );
+    }
+    assert!(
+        timings[1] < timings[0] * 20,
+        "percentage retreats made the mixture {:.1}x slower",
+        timings[1].as_secs_f64() / timings[0].as_secs_f64()
+    );
+}
@@ -0,0 +1,181 @@
+use crate::{run_battle, WarOdds, WarWeights};
+
+// What is known about the size of one army, from an exact count down to a
+// scout's rough estimate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArmySize {
+    Exact(usize),
+    Uniform { min: usize, max: usize },
+    Triangular { min: usize, mode: usize, max: usize },
+    Weighted(Vec<(usize, f64)>),
+}
+
+impl ArmySize {
+    // Each possible size with its probability, in increasing order of size.
+    // Empty if no size has any weight.
+    pub fn get_distribution(&self) -> Vec<(usize, f64)> {
+        let weighted: Vec<(usize, f64)> = match self {
+            ArmySize::Exact(size) => vec![(*size, 1.)],
+            ArmySize::Uniform { min, max } => {
+                let (min, max) = (*min.min(max), *max.max(min));
+                (min..=max).map(|size| (size, 1.)).collect()
+            }
+            ArmySize::Triangular { min, mode, max } => {
+                let (min, max) = (*min.min(max), *max.max(min));
+                let mode = (*mode).clamp(min, max);
+                (min..=max)
+                    .map(|size| {
+                        let weight = if size <= mode {
+                            (size - min + 1) as f64 / (mode - min + 1) as f64
+                        } else {
+                            (max - size + 1) as f64 / (max - mode + 1) as f64
+                        };
+                        (size, weight)
+                    })
+                    .collect()
+            }
+            ArmySize::Weighted(sizes) => {
+                let mut sizes: Vec<(usize, f64)> = sizes
+                    .iter()
+                    .filter(|(_, weight)| *weight > 0.)
+                    .copied()
+                    .collect();
+                sizes.sort_by_key(|(size, _)| *size);
+                sizes
+            }
+        };
+        let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+        weighted
+            .into_iter()
+            .map(|(size, weight)| (size, weight / total))
+            .collect()
+    }
+
+    pub fn get_max(&self) -> usize {
+        self.get_distribution()
+            .last()
+            .map(|(size, _)| *size)
+            .unwrap_or(0)
+    }
+
+    pub fn get_expected(&self) -> f64 {
+        self.get_distribution()
+            .iter()
+            .map(|(size, chance)| *size as f64 * chance)
+            .sum()
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.get_distribution().len() == 1
+    }
+}
+
+impl WarWeights {
+    // Every combination of the two sides' sizes, weighted by how likely both
+    // are at once.
+    pub fn from_sizes(attackers: &ArmySize, defenders: &ArmySize) -> Self {
+        let defender_sizes = defenders.get_distribution();
+        let mut weights = Self::new(attackers.get_max(), defenders.get_max());
+        for (attacker_count, attacker_chance) in attackers.get_distribution() {
+            for (defender_count, defender_chance) in &defender_sizes {
+                weights.add(
+                    attacker_count,
+                    *defender_count,
+                    attacker_chance * defender_chance,
+                );
+            }
+        }
+        weights
+    }
+}
+
+// The battle is linear in its starting weights, so running the whole mixture
+// at once gives the same answer as running every pair of sizes on its own and
+// blending the results.
+pub fn calculate_mixture(attackers: &ArmySize, defenders: &ArmySize, odds: &WarOdds) -> WarWeights {
+    run_battle(WarWeights::from_sizes(attackers, defenders), odds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_weights, RetreatThreshold, Side};
+
+    #[test]
+    fn test_distributions() {
+        let triangular = ArmySize::Triangular {
+            min: 10,
+            mode: 12,
+            max: 13,
+        };
+        let distribution = triangular.get_distribution();
+        let weights: Vec<f64> = distribution
+            .iter()
+            .map(|(_, chance)| chance * 7.5)
+            .collect();
+        let expected = [1., 2., 3., 1.5];
+        for (weight, expected) in weights.iter().zip(expected) {
+            assert!((weight - expected).abs() < 1e-12);
+        }
+        assert_eq!(triangular.get_max(), 13);
+
+        let uniform = ArmySize::Uniform { min: 90, max: 70 };
+        assert_eq!(uniform.get_distribution().len(), 21);
+        assert!((uniform.get_expected() - 80.).abs() < 1e-12);
+
+        let weighted = ArmySize::Weighted(vec![(80, 2.), (70, 1.), (90, 1.), (100, 0.)]);
+        assert_eq!(
+            weighted.get_distribution(),
+            vec![(70, 0.25), (80, 0.5), (90, 0.25)]
+        );
+        assert!(ArmySize::Exact(5).is_exact());
+        assert!(ArmySize::Weighted(vec![]).get_distribution().is_empty());
+    }
+
+    #[test]
+    fn test_mixture_blends_each_size() {
+        let odds = WarOdds::default();
+        let attackers = ArmySize::Exact(30);
+        let defenders = ArmySize::Weighted(vec![(20, 1.), (35, 3.)]);
+        let mixture = calculate_mixture(&attackers, &defenders, &odds);
+        let low = calculate_weights(30., 20., &odds);
+        let high = calculate_weights(30., 35., &odds);
+        let blended =
+            0.25 * low.get_odds_of_attacker_win() + 0.75 * high.get_odds_of_attacker_win();
+        assert!((mixture.get_odds_of_attacker_win() - blended).abs() < 1e-9);
+        let blended = 0.25 * low.get_expected_attackers().unwrap() * low.total()
+            + 0.75 * high.get_expected_attackers().unwrap() * high.total();
+        assert!(
+            (mixture.get_expected_attackers().unwrap() * mixture.total() - blended).abs() < 1e-9
+        );
+    }
+
+    // Percentage retreats depend on each battle's own starting strength, not
+    // on the largest army the mixture allows.
+    #[test]
+    fn test_percent_retreats_blend_each_size() {
+        let odds = WarOdds {
+            attacker_retreat: Some(RetreatThreshold::Percent(50.)),
+            // Sizes that share a threshold are fought together, where the
+            // smaller weights would be pruned differently.
+            prune_epsilon: 0.,
+            ..WarOdds::default()
+        };
+        let attackers = ArmySize::Uniform { min: 30, max: 45 };
+        let mixture = calculate_mixture(&attackers, &ArmySize::Exact(40), &odds);
+        let mut blended = WarWeights::new(0, 0);
+        for (size, chance) in attackers.get_distribution() {
+            blended.add_weighted(&calculate_weights(size as f64, 40., &odds), chance);
+        }
+        for side in [Side::Attacker, Side::Defender] {
+            assert!(
+                (mixture.get_odds_of_retreat(side) - blended.get_odds_of_retreat(side)).abs()
+                    < 1e-9
+            );
+        }
+        assert!(
+            (mixture.get_odds_of_attacker_win() - blended.get_odds_of_attacker_win()).abs() < 1e-9
+        );
+        assert!(mixture.get_odds_of_retreat(Side::Attacker) > 0.1);
+    }
+}
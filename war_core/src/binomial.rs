@@ -0,0 +1,90 @@
+// Probability of every success count in 0..=trials. The row is built outwards
+// from the mode with the ratio between neighbouring terms and normalized at the
+// end, so nothing is rounded and no intermediate value can overflow.
+pub fn binomial_pmf(trials: usize, rate: f64) -> Vec<f64> {
+    let rate = rate.clamp(0., 1.);
+    let mut pmf = vec![0.; trials + 1];
+    if rate == 0. {
+        pmf[0] = 1.;
+        return pmf;
+    }
+    if rate == 1. {
+        pmf[trials] = 1.;
+        return pmf;
+    }
+    let mode = (((trials + 1) as f64 * rate).floor() as usize).min(trials);
+    pmf[mode] = 1.;
+    let odds = rate / (1. - rate);
+    for successes in mode..trials {
+        pmf[successes + 1] =
+            pmf[successes] * odds * (trials - successes) as f64 / (successes + 1) as f64;
+    }
+    for successes in (1..=mode).rev() {
+        pmf[successes - 1] =
+            pmf[successes] / odds * successes as f64 / (trials - successes + 1) as f64;
+    }
+    let total: f64 = pmf.iter().sum();
+    for chance in pmf.iter_mut() {
+        *chance /= total;
+    }
+    pmf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(left: f64, right: f64, tolerance: f64) {
+        assert!(
+            (left - right).abs() <= right.abs() * tolerance,
+            "{} is not close to {}",
+            left,
+            right
+        );
+    }
+
+    #[test]
+    fn test_rows_sum_to_one() {
+        for trials in [1, 7, 22, 100, 400, 2000] {
+            for rate in [0.01, 0.1, 0.13, 0.5, 0.87, 0.99] {
+                let sum: f64 = binomial_pmf(trials, rate).iter().sum();
+                assert_close(sum, 1., 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_rational_values() {
+        // Quarters are exact in binary, so the row is 3^(4-k) * C(4, k) / 256.
+        let expected = [81., 108., 54., 12., 1.].map(|n| n / 256.);
+        for (value, expected) in binomial_pmf(4, 0.25).iter().zip(expected) {
+            assert_close(*value, expected, 1e-14);
+        }
+        // C(10, k) / 1024 for a fair coin.
+        let expected =
+            [1., 10., 45., 120., 210., 252., 210., 120., 45., 10., 1.].map(|n| n / 1024.);
+        for (value, expected) in binomial_pmf(10, 0.5).iter().zip(expected) {
+            assert_close(*value, expected, 1e-14);
+        }
+        // C(100, 14) * 0.5^100, where the old rounded product went wrong.
+        assert_close(
+            binomial_pmf(100, 0.5)[14],
+            44186942677323600. / 2f64.powi(100),
+            1e-13,
+        );
+        // C(200, 100) / 2^200.
+        assert_close(
+            binomial_pmf(200, 0.5)[100],
+            90548514656103281165404177077484163874504589675413336841320. / 2f64.powi(200),
+            1e-13,
+        );
+    }
+
+    #[test]
+    fn test_degenerate_rates() {
+        assert_eq!(binomial_pmf(3, 0.), vec![1., 0., 0., 0.]);
+        assert_eq!(binomial_pmf(3, 1.), vec![0., 0., 0., 1.]);
+        assert_eq!(binomial_pmf(3, -0.05), vec![1., 0., 0., 0.]);
+        assert_eq!(binomial_pmf(0, 0.3), vec![1.]);
+    }
+}
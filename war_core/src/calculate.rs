@@ -0,0 +1,1026 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    binomial_pmf, blend_histories, ResolutionOrder, RetreatThreshold, Side, WarOdds, WarWeights,
+};
+
+// The central part of a kill distribution. Kill counts in the tails are only
+// dropped while their combined chance stays within the tolerance.
+struct KillOdds {
+    min_kills: usize,
+    chances: Vec<f64>,
+    lower_tail: f64,
+    upper_tail: f64,
+}
+
+impl KillOdds {
+    fn new(engagements: usize, rate: f64, tolerance: f64) -> Self {
+        let chances = binomial_pmf(engagements, rate);
+        let mut min_kills = 0;
+        let mut max_kills = engagements;
+        let mut lower_tail = 0.;
+        let mut upper_tail = 0.;
+        while min_kills < max_kills && lower_tail + chances[min_kills] <= tolerance / 2. {
+            lower_tail += chances[min_kills];
+            min_kills += 1;
+        }
+        while max_kills > min_kills && upper_tail + chances[max_kills] <= tolerance / 2. {
+            upper_tail += chances[max_kills];
+            max_kills -= 1;
+        }
+        Self {
+            min_kills,
+            chances: chances[min_kills..=max_kills].to_vec(),
+            lower_tail,
+            upper_tail,
+        }
+    }
+
+    fn max_kills(&self) -> usize {
+        self.min_kills + self.chances.len() - 1
+    }
+
+    fn kills(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.chances
+            .iter()
+            .enumerate()
+            .map(|(offset, chance)| (self.min_kills + offset, *chance))
+    }
+
+    // Survivor counts on the side under fire, with their chances. Kills past
+    // the window wipe the targets out anyway once they are in reach.
+    fn survivors(&self, targets: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let wiped_out = if targets <= self.max_kills() + 1 {
+            self.upper_tail
+        } else {
+            0.
+        };
+        self.kills()
+            .map(move |(kills, chance)| (targets.saturating_sub(kills), chance))
+            .chain(std::iter::once((0, wiped_out)))
+    }
+
+    // Mass that cannot be placed when the side under fire has `targets` left.
+    fn discarded(&self, targets: usize) -> f64 {
+        if targets <= self.max_kills() + 1 {
+            self.lower_tail
+        } else {
+            self.lower_tail + self.upper_tail
+        }
+    }
+}
+
+// Kill distributions for one side, built lazily for each engagement count.
+struct KillKernels {
+    rate: f64,
+    tolerance: f64,
+    by_engagements: BTreeMap<usize, KillOdds>,
+}
+
+impl KillKernels {
+    fn new(rate: f64, tolerance: f64) -> Self {
+        Self {
+            rate,
+            tolerance,
+            by_engagements: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, engagements: usize) -> &KillOdds {
+        let (rate, tolerance) = (self.rate, self.tolerance);
+        self.by_engagements
+            .entry(engagements)
+            .or_insert_with(|| KillOdds::new(engagements, rate, tolerance))
+    }
+}
+
+fn attacker_volley(weights: &WarWeights, odds: &WarOdds, kernels: &mut KillKernels) -> WarWeights {
+    let mut new_weights = WarWeights::empty_like(weights);
+    for (attackers, defenders, weight) in weights.iter() {
+        if attackers == 0 || defenders == 0 {
+            new_weights.add(attackers, defenders, weight);
+            continue;
+        }
+        let kill_odds = kernels.get(odds.get_engaged(attackers));
+        for (new_defenders, chance) in kill_odds.survivors(defenders) {
+            new_weights.add(attackers, new_defenders, weight * chance);
+        }
+        new_weights.discard(weight * kill_odds.discarded(defenders));
+    }
+    new_weights
+}
+
+fn defender_volley(weights: &WarWeights, odds: &WarOdds, kernels: &mut KillKernels) -> WarWeights {
+    let mut new_weights = WarWeights::empty_like(weights);
+    for (attackers, defenders, weight) in weights.iter() {
+        if attackers == 0 || defenders == 0 {
+            new_weights.add(attackers, defenders, weight);
+            continue;
+        }
+        let kill_odds = kernels.get(odds.get_engaged(defenders));
+        for (new_attackers, chance) in kill_odds.survivors(attackers) {
+            new_weights.add(new_attackers, defenders, weight * chance);
+        }
+        new_weights.discard(weight * kill_odds.discarded(attackers));
+    }
+    new_weights
+}
+
+// Both sides fire at the strength they had at the start of the round, so the
+// kill counts are independent and the transition is their joint distribution.
+// `engaged` gives how many of a side's soldiers get to fire.
+fn simultaneous_volley(
+    weights: &WarWeights,
+    attacker_kernels: &mut KillKernels,
+    defender_kernels: &mut KillKernels,
+    engaged: impl Fn(usize) -> usize,
+) -> WarWeights {
+    let mut new_weights = WarWeights::empty_like(weights);
+    for (attackers, defenders, weight) in weights.iter() {
+        if attackers == 0 || defenders == 0 {
+            new_weights.add(attackers, defenders, weight);
+            continue;
+        }
+        let attacker_kills = attacker_kernels.get(engaged(attackers));
+        let defender_kills = defender_kernels.get(engaged(defenders));
+        for (new_defenders, attacker_chance) in attacker_kills.survivors(defenders) {
+            for (new_attackers, defender_chance) in defender_kills.survivors(attackers) {
+                new_weights.add(
+                    new_attackers,
+                    new_defenders,
+                    weight * attacker_chance * defender_chance,
+                );
+            }
+        }
+        let attacker_kept = 1. - attacker_kills.discarded(defenders);
+        let defender_kept = 1. - defender_kills.discarded(attackers);
+        new_weights.discard(weight * (1. - attacker_kept * defender_kept));
+    }
+    new_weights
+}
+
+// Kill distributions for both sides, shared across rounds and cells as long
+// as the odds stay the same.
+pub(crate) struct BattleKernels {
+    attacker: KillKernels,
+    defender: KillKernels,
+    attacker_volley: KillKernels,
+    defender_volley: KillKernels,
+    // Soldier counts each side retreats below, zero if it never does.
+    retreat_below: (usize, usize),
+}
+
+impl BattleKernels {
+    pub(crate) fn new(odds: &WarOdds) -> Self {
+        Self {
+            attacker: KillKernels::new(odds.get_attacker_rate(), odds.kill_tolerance),
+            defender: KillKernels::new(odds.get_defender_rate(), odds.kill_tolerance),
+            attacker_volley: KillKernels::new(
+                odds.get_volley_rate(Side::Attacker),
+                odds.kill_tolerance,
+            ),
+            defender_volley: KillKernels::new(
+                odds.get_volley_rate(Side::Defender),
+                odds.kill_tolerance,
+            ),
+            retreat_below: (0, 0),
+        }
+    }
+
+    // Sets the retreat thresholds against the strength each side started
+    // this battle with. Must be called before the first round of each battle
+    // the kernels are used for.
+    pub(crate) fn start_battle(&mut self, started_with: (usize, usize), odds: &WarOdds) {
+        self.retreat_below = get_retreat_below(started_with, odds);
+    }
+}
+
+// Soldier counts each side retreats below in a battle that started with
+// `started_with`, zero if it never does.
+fn get_retreat_below(started_with: (usize, usize), odds: &WarOdds) -> (usize, usize) {
+    let below = |side, starting| {
+        odds.get_retreat(side)
+            .map(|threshold: RetreatThreshold| threshold.get_soldiers(starting))
+            .unwrap_or(0)
+    };
+    (
+        below(Side::Attacker, started_with.0),
+        below(Side::Defender, started_with.1),
+    )
+}
+
+// Fights `weights` with `fight`, given the strength the battle started with.
+// Without `started_with` every cell is a battle starting now, and as
+// percentage retreat thresholds differ between starting sizes, the cells are
+// grouped by the thresholds they give, each group fought as one battle from
+// any of its sizes and the histories blended.
+pub(crate) fn fight_from_each_start(
+    weights: WarWeights,
+    started_with: Option<(usize, usize)>,
+    odds: &WarOdds,
+    mut fight: impl FnMut(WarWeights, (usize, usize)) -> Vec<WarWeights>,
+) -> Vec<WarWeights> {
+    if let Some(started_with) = started_with {
+        return fight(weights, started_with);
+    }
+    let largest = (weights.max_attackers, weights.max_defenders);
+    if !odds.has_relative_retreat() {
+        let started_with = weights
+            .iter()
+            .next()
+            .map_or(largest, |(attackers, defenders, _)| (attackers, defenders));
+        return fight(weights, started_with);
+    }
+    let mut groups: BTreeMap<(usize, usize), ((usize, usize), WarWeights)> = BTreeMap::new();
+    for (attackers, defenders, weight) in weights.iter() {
+        let (_, group) = groups
+            .entry(get_retreat_below((attackers, defenders), odds))
+            .or_insert_with(|| {
+                (
+                    (attackers, defenders),
+                    WarWeights::new(largest.0, largest.1),
+                )
+            });
+        group.add(attackers, defenders, weight);
+    }
+    if groups.len() <= 1 {
+        let started_with = groups
+            .values()
+            .next()
+            .map_or(largest, |(started_with, _)| *started_with);
+        return fight(weights, started_with);
+    }
+    // Retreats and discarded mass from before carry over as they are.
+    let mut histories = vec![(vec![WarWeights::empty_like(&weights)], 1.)];
+    for (started_with, group) in groups.into_values() {
+        histories.push((fight(group, started_with), 1.));
+    }
+    blend_histories(&histories)
+}
+
+pub(crate) fn step_battle_with(
+    weights: &WarWeights,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    let mut new_weights = match odds.resolution_order {
+        ResolutionOrder::AttackerFirst => {
+            let mut new_weights = attacker_volley(weights, odds, &mut kernels.attacker);
+            new_weights.prune(odds.prune_epsilon);
+            let mut new_weights = defender_volley(&new_weights, odds, &mut kernels.defender);
+            new_weights.prune(odds.prune_epsilon);
+            new_weights
+        }
+        ResolutionOrder::DefenderFirst => {
+            let mut new_weights = defender_volley(weights, odds, &mut kernels.defender);
+            new_weights.prune(odds.prune_epsilon);
+            let mut new_weights = attacker_volley(&new_weights, odds, &mut kernels.attacker);
+            new_weights.prune(odds.prune_epsilon);
+            new_weights
+        }
+        ResolutionOrder::Simultaneous => {
+            let mut new_weights = simultaneous_volley(
+                weights,
+                &mut kernels.attacker,
+                &mut kernels.defender,
+                |soldiers| odds.get_engaged(soldiers),
+            );
+            new_weights.prune(odds.prune_epsilon);
+            new_weights
+        }
+    };
+    let (attackers_below, defenders_below) = kernels.retreat_below;
+    new_weights.retreat_below(attackers_below, defenders_below);
+    new_weights
+}
+
+// One volley round, before the lines close. Both sides' archers shoot at the
+// same time and a side without them only takes fire. Archers shoot over the
+// front line, so every one of them fires whatever the frontage.
+fn fire_volley_with(
+    weights: &WarWeights,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    let mut new_weights = simultaneous_volley(
+        weights,
+        &mut kernels.attacker_volley,
+        &mut kernels.defender_volley,
+        |soldiers| soldiers,
+    );
+    new_weights.prune(odds.prune_epsilon);
+    let (attackers_below, defenders_below) = kernels.retreat_below;
+    new_weights.retreat_below(attackers_below, defenders_below);
+    new_weights
+}
+
+// The state after each volley round.
+pub(crate) fn fire_volleys_with(
+    weights: &WarWeights,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> Vec<WarWeights> {
+    let mut volleys: Vec<WarWeights> = Vec::new();
+    for _ in 0..odds.get_volley_rounds() {
+        let new_weights = fire_volley_with(volleys.last().unwrap_or(weights), odds, kernels);
+        volleys.push(new_weights);
+    }
+    volleys
+}
+
+// The volley rounds of a battle starting from `weights`, with the starting
+// state left out.
+pub fn fire_volleys(weights: &WarWeights, odds: &WarOdds) -> Vec<WarWeights> {
+    let mut kernels = BattleKernels::new(odds);
+    let mut history =
+        fight_from_each_start(weights.clone(), None, odds, |weights, started_with| {
+            kernels.start_battle(started_with, odds);
+            let mut history = vec![weights];
+            let volleys = fire_volleys_with(&history[0], odds, &mut kernels);
+            history.extend(volleys);
+            history
+        });
+    history.remove(0);
+    history
+}
+
+// Backward counterpart of one side firing: the value of every state before
+// `side` fires, given the value of every state after. Values are indexed as
+// `attackers * (max_defenders + 1) + defenders`, and battles that are over
+// keep theirs. Discarded mass is worth nothing.
+fn back_up_volley(
+    values: &[f64],
+    max_defenders: usize,
+    side: Side,
+    kernels: &mut KillKernels,
+    engaged: impl Fn(usize) -> usize,
+) -> Vec<f64> {
+    let width = max_defenders + 1;
+    let mut previous = values.to_vec();
+    for (cell, value) in previous.iter_mut().enumerate() {
+        let (attackers, defenders) = (cell / width, cell % width);
+        if attackers == 0 || defenders == 0 {
+            continue;
+        }
+        *value = match side {
+            Side::Attacker => kernels
+                .get(engaged(attackers))
+                .survivors(defenders)
+                .map(|(new_defenders, chance)| chance * values[attackers * width + new_defenders])
+                .sum(),
+            Side::Defender => kernels
+                .get(engaged(defenders))
+                .survivors(attackers)
+                .map(|(new_attackers, chance)| chance * values[new_attackers * width + defenders])
+                .sum(),
+        };
+    }
+    previous
+}
+
+// Backward counterpart of `simultaneous_volley`.
+fn back_up_simultaneous_volley(
+    values: &[f64],
+    max_defenders: usize,
+    attacker_kernels: &mut KillKernels,
+    defender_kernels: &mut KillKernels,
+    engaged: impl Fn(usize) -> usize,
+) -> Vec<f64> {
+    let width = max_defenders + 1;
+    let mut previous = values.to_vec();
+    for (cell, value) in previous.iter_mut().enumerate() {
+        let (attackers, defenders) = (cell / width, cell % width);
+        if attackers == 0 || defenders == 0 {
+            continue;
+        }
+        let attacker_kills = attacker_kernels.get(engaged(attackers));
+        let defender_kills = defender_kernels.get(engaged(defenders));
+        *value = attacker_kills
+            .survivors(defenders)
+            .map(|(new_defenders, attacker_chance)| {
+                attacker_chance
+                    * defender_kills
+                        .survivors(attackers)
+                        .map(|(new_attackers, defender_chance)| {
+                            defender_chance * values[new_attackers * width + new_defenders]
+                        })
+                        .sum::<f64>()
+            })
+            .sum();
+    }
+    previous
+}
+
+// Battles that would retreat once the round is over take `retreat_value` of
+// the soldiers left on each side instead.
+fn back_up_retreats(
+    values: &[f64],
+    max_defenders: usize,
+    retreat_value: impl Fn(usize, usize) -> f64,
+    kernels: &BattleKernels,
+) -> Vec<f64> {
+    let width = max_defenders + 1;
+    let (attackers_below, defenders_below) = kernels.retreat_below;
+    values
+        .iter()
+        .enumerate()
+        .map(|(cell, value)| {
+            let (attackers, defenders) = (cell / width, cell % width);
+            let fighting = attackers > 0 && defenders > 0;
+            if fighting && (attackers < attackers_below || defenders < defenders_below) {
+                retreat_value(attackers, defenders)
+            } else {
+                *value
+            }
+        })
+        .collect()
+}
+
+// Backward counterpart of `step_battle_with`, for solving from the end of a
+// battle back to its start. The sides fire one after the other in most
+// orders, so each state only sums over one side's kills at a time.
+pub(crate) fn back_up_round_with(
+    values: &[f64],
+    max_defenders: usize,
+    retreat_value: impl Fn(usize, usize) -> f64,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> Vec<f64> {
+    let values = back_up_retreats(values, max_defenders, retreat_value, kernels);
+    let engaged = |soldiers| odds.get_engaged(soldiers);
+    match odds.resolution_order {
+        ResolutionOrder::AttackerFirst => {
+            let values = back_up_volley(
+                &values,
+                max_defenders,
+                Side::Defender,
+                &mut kernels.defender,
+                engaged,
+            );
+            back_up_volley(
+                &values,
+                max_defenders,
+                Side::Attacker,
+                &mut kernels.attacker,
+                engaged,
+            )
+        }
+        ResolutionOrder::DefenderFirst => {
+            let values = back_up_volley(
+                &values,
+                max_defenders,
+                Side::Attacker,
+                &mut kernels.attacker,
+                engaged,
+            );
+            back_up_volley(
+                &values,
+                max_defenders,
+                Side::Defender,
+                &mut kernels.defender,
+                engaged,
+            )
+        }
+        ResolutionOrder::Simultaneous => back_up_simultaneous_volley(
+            &values,
+            max_defenders,
+            &mut kernels.attacker,
+            &mut kernels.defender,
+            engaged,
+        ),
+    }
+}
+
+// Backward counterpart of `fire_volley_with`.
+pub(crate) fn back_up_archer_volley_with(
+    values: &[f64],
+    max_defenders: usize,
+    retreat_value: impl Fn(usize, usize) -> f64,
+    kernels: &mut BattleKernels,
+) -> Vec<f64> {
+    let values = back_up_retreats(values, max_defenders, retreat_value, kernels);
+    back_up_simultaneous_volley(
+        &values,
+        max_defenders,
+        &mut kernels.attacker_volley,
+        &mut kernels.defender_volley,
+        |soldiers| soldiers,
+    )
+}
+
+// Backward counterpart of bringing in the reinforcements due at the start of
+// round `round`: a state before they join is worth what the state they lead
+// to is, capped at the grid like `WarWeights::reinforce`.
+pub(crate) fn back_up_arrivals(
+    values: &[f64],
+    max_attackers: usize,
+    max_defenders: usize,
+    round: usize,
+    odds: &WarOdds,
+) -> Vec<f64> {
+    let arriving = |side| {
+        odds.get_arrivals(round)
+            .filter(|reinforcement| reinforcement.side == side)
+            .map(|reinforcement| reinforcement.soldiers)
+            .sum::<usize>()
+    };
+    let (new_attackers, new_defenders) = (arriving(Side::Attacker), arriving(Side::Defender));
+    if new_attackers == 0 && new_defenders == 0 {
+        return values.to_vec();
+    }
+    let width = max_defenders + 1;
+    (0..values.len())
+        .map(|cell| {
+            let (attackers, defenders) = (cell / width, cell % width);
+            if attackers == 0 || defenders == 0 {
+                return values[cell];
+            }
+            let attackers = (attackers + new_attackers).min(max_attackers);
+            let defenders = (defenders + new_defenders).min(max_defenders);
+            values[attackers * width + defenders]
+        })
+        .collect()
+}
+
+// Widens the grid for the reinforcements still to come after
+// `rounds_elapsed`, volley rounds included, so capping them at the grid size
+// never cuts them short.
+pub(crate) fn make_room_for_reinforcements(
+    weights: &mut WarWeights,
+    rounds_elapsed: usize,
+    odds: &WarOdds,
+) {
+    let melee_rounds = odds.get_melee_rounds_elapsed(rounds_elapsed);
+    weights.grow(
+        weights.max_attackers + odds.get_reinforcements_after(Side::Attacker, melee_rounds),
+        weights.max_defenders + odds.get_reinforcements_after(Side::Defender, melee_rounds),
+    );
+}
+
+pub fn step_battle(weights: &WarWeights, odds: &WarOdds) -> WarWeights {
+    let mut kernels = BattleKernels::new(odds);
+    fight_from_each_start(weights.clone(), None, odds, |weights, started_with| {
+        kernels.start_battle(started_with, odds);
+        vec![step_battle_with(&weights, odds, &mut kernels)]
+    })
+    .pop()
+    .unwrap()
+}
+
+// Fights round number `round`, counting from one, after bringing in any
+// reinforcements due at its start.
+pub(crate) fn fight_round_with(
+    weights: &WarWeights,
+    round: usize,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    if odds.get_arrivals(round).next().is_none() {
+        return step_battle_with(weights, odds, kernels);
+    }
+    let mut weights = weights.clone();
+    for reinforcement in odds.get_arrivals(round) {
+        weights.reinforce(reinforcement.side, reinforcement.soldiers);
+    }
+    step_battle_with(&weights, odds, kernels)
+}
+
+// Fights the round after the first `rounds_elapsed`, which count the volley
+// rounds too: a volley while any are left and melee after that.
+pub(crate) fn fight_next_round_with(
+    weights: &WarWeights,
+    rounds_elapsed: usize,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    if rounds_elapsed < odds.get_volley_rounds() {
+        fire_volley_with(weights, odds, kernels)
+    } else {
+        let round = odds.get_melee_rounds_elapsed(rounds_elapsed) + 1;
+        fight_round_with(weights, round, odds, kernels)
+    }
+}
+
+pub fn calculate_weights(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+) -> WarWeights {
+    run_battle(
+        WarWeights::starting(starting_attackers as usize, starting_defenders as usize),
+        odds,
+    )
+}
+
+// Fires any volleys and fights `round_count` rounds from any starting
+// distribution.
+pub fn run_battle(weights: WarWeights, odds: &WarOdds) -> WarWeights {
+    run_battle_with(weights, odds, &mut BattleKernels::new(odds))
+}
+
+pub(crate) fn run_battle_with(
+    weights: WarWeights,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    fight_from_each_start(weights, None, odds, |mut weights, started_with| {
+        kernels.start_battle(started_with, odds);
+        make_room_for_reinforcements(&mut weights, 0, odds);
+        if let Some(volleyed) = fire_volleys_with(&weights, odds, kernels).pop() {
+            weights = volleyed;
+        }
+        for round in 1..=odds.round_count {
+            weights = fight_round_with(&weights, round, odds, kernels);
+        }
+        vec![weights]
+    })
+    .pop()
+    .unwrap()
+}
+
+// The distribution at the start of the battle and after every round, so
+// `history[round]` is the state once `round` rounds have been fought. Volley
+// rounds come first and count as rounds here.
+pub fn calculate_history(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+) -> Vec<WarWeights> {
+    run_history(
+        WarWeights::starting(starting_attackers as usize, starting_defenders as usize),
+        odds,
+    )
+}
+
+pub fn run_history(weights: WarWeights, odds: &WarOdds) -> Vec<WarWeights> {
+    resume_battle(weights, 0, None, odds)
+}
+
+// Picks up a battle that has already been going for `rounds_elapsed` rounds,
+// counted like `calculate_history` with the volley rounds first, and fights
+// whatever volleys and melee rounds are left. Percentage retreat thresholds are measured
+// against `started_with`, or against `current` if the battle starts now.
+pub fn resume_battle(
+    current: WarWeights,
+    rounds_elapsed: usize,
+    started_with: Option<(usize, usize)>,
+    odds: &WarOdds,
+) -> Vec<WarWeights> {
+    let mut kernels = BattleKernels::new(odds);
+    fight_from_each_start(current, started_with, odds, |mut current, started_with| {
+        kernels.start_battle(started_with, odds);
+        make_room_for_reinforcements(&mut current, rounds_elapsed, odds);
+        let mut history = vec![current];
+        for elapsed in rounds_elapsed..odds.get_volley_rounds() + odds.round_count {
+            let weights =
+                fight_next_round_with(history.last().unwrap(), elapsed, odds, &mut kernels);
+            history.push(weights);
+        }
+        history
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reinforcement, Toggle};
+
+    fn assert_close(left: f64, right: f64) {
+        assert!(
+            (left - right).abs() <= right.abs() * 1e-12,
+            "{} is not close to {}",
+            left,
+            right
+        );
+    }
+
+    #[test]
+    fn test_kill_odds() {
+        let kill_odds = KillOdds::new(100, 0.1, 0.);
+        assert_eq!(kill_odds.min_kills, 0);
+        let chances = kill_odds.chances;
+        assert_close(chances[1], 0.0002951266543065283);
+        assert_close(chances[2], 0.0016231965986859057);
+        assert_close(chances[3], 0.005891602469304398);
+        assert_close(chances[4], 0.015874595542292404);
+        assert_close(chances[5], 0.03386580382355713);
+        assert_close(chances[6], 0.05957872894885052);
+        assert_close(chances[7], 0.08889524636812617);
+        assert_close(chances[8], 0.11482302655882966);
+        assert_close(chances[9], 0.13041627707916453);
+        assert_close(chances[10], 0.13186534682448858);
+        assert_close(chances[11], 0.11987758802226234);
+        assert_close(chances[12], 0.09878801235167915);
+        assert_close(chances[13], 0.0743020947602373);
+        assert_close(chances[14], 0.05130382733444958);
+        assert_close(chances[15], 0.03268243815379751);
+        assert_close(chances[16], 0.019291716965783256);
+        assert_close(chances[17], 0.010591530883175122);
+        assert_close(chances[18], 0.005426525082120584);
+        assert_close(chances[19], 0.0026021933142332644);
+        assert_close(chances[20], 0.001170986991404969);
+    }
+
+    #[test]
+    fn test_step_battle() {
+        let mut weights = WarWeights::new(100, 100);
+        weights.add(100, 100, 1.);
+        let odds = WarOdds::default();
+        let new_weights = step_battle(&weights, &odds);
+        assert_close(new_weights.get(90, 86), 0.014479886404248275);
+    }
+
+    #[test]
+    fn test_calculate_weights_large_armies() {
+        let odds = WarOdds::default();
+        let weights = calculate_weights(250., 400., &odds);
+        assert_eq!(weights.get_attackers_winning_results().len(), 251);
+        assert_eq!(weights.get_defenders_winning_results().len(), 401);
+        assert!(weights.get_discarded_mass() < 1e-6);
+        assert_close(weights.total() + weights.get_discarded_mass(), 1.);
+    }
+
+    #[test]
+    fn test_step_battle_prunes_negligible_cells() {
+        let mut weights = WarWeights::new(100, 100);
+        weights.add(100, 100, 1.);
+        let odds = WarOdds {
+            prune_epsilon: 1e-6,
+            ..WarOdds::default()
+        };
+        let new_weights = step_battle(&weights, &odds);
+        assert!(new_weights.iter().all(|(_, _, weight)| weight >= 1e-6));
+        assert!(new_weights.get_discarded_mass() > 0.);
+        assert_close(new_weights.total() + new_weights.get_discarded_mass(), 1.);
+    }
+
+    #[test]
+    fn test_discarded_mass_is_reported() {
+        let odds = WarOdds {
+            base_chance: 60.,
+            kill_tolerance: 1e-6,
+            ..WarOdds::default()
+        };
+        let weights = calculate_weights(200., 200., &odds);
+        let discarded = weights.get_discarded_mass();
+        assert!(discarded > 0.);
+        assert!(discarded < 1e-4);
+        assert_close(weights.total() + discarded, 1.);
+    }
+
+    #[test]
+    fn test_resolution_orders() {
+        let mut weights = WarWeights::new(1, 1);
+        weights.add(1, 1, 1.);
+        let odds = WarOdds {
+            base_chance: 50.,
+            attacker_present: false,
+            attacker_blessed: false,
+            defender_blessed: false,
+            kill_tolerance: 0.,
+            prune_epsilon: 0.,
+            ..WarOdds::default()
+        };
+
+        let attacker_first = step_battle(&weights, &odds);
+        assert_eq!(attacker_first.get(1, 0), 0.5);
+        assert_eq!(attacker_first.get(0, 1), 0.25);
+        assert_eq!(attacker_first.get(1, 1), 0.25);
+
+        let defender_first = step_battle(
+            &weights,
+            &WarOdds {
+                resolution_order: ResolutionOrder::DefenderFirst,
+                ..odds.clone()
+            },
+        );
+        assert_eq!(defender_first.get(0, 1), 0.5);
+        assert_eq!(defender_first.get(1, 0), 0.25);
+        assert_eq!(defender_first.get(1, 1), 0.25);
+
+        let simultaneous = step_battle(
+            &weights,
+            &WarOdds {
+                resolution_order: ResolutionOrder::Simultaneous,
+                ..odds
+            },
+        );
+        assert_eq!(simultaneous.get(0, 0), 0.25);
+        assert_eq!(simultaneous.get(1, 0), 0.25);
+        assert_eq!(simultaneous.get(0, 1), 0.25);
+        assert_eq!(simultaneous.get(1, 1), 0.25);
+        assert_eq!(simultaneous.get_odds_of_mutual_destruction(), 0.25);
+        assert_eq!(simultaneous.get_attackers_winning_results(), vec![0., 0.25]);
+    }
+
+    #[test]
+    fn test_calculate_history() {
+        let odds = WarOdds {
+            round_count: 12,
+            ..WarOdds::default()
+        };
+        let history = calculate_history(60., 50., &odds);
+        assert_eq!(history.len(), 13);
+        assert_eq!(history[0].get(60, 50), 1.);
+        let last = history.last().unwrap();
+        let weights = calculate_weights(60., 50., &odds);
+        assert_eq!(
+            last.get_attackers_winning_results(),
+            weights.get_attackers_winning_results()
+        );
+        for rounds in history.windows(2) {
+            assert!(rounds[1].get_odds_of_attacker_win() >= rounds[0].get_odds_of_attacker_win());
+            assert!(rounds[1].get_odds_of_defender_win() >= rounds[0].get_odds_of_defender_win());
+        }
+    }
+
+    #[test]
+    fn test_resume_battle() {
+        let odds = WarOdds::default();
+        let history = calculate_history(40., 35., &odds);
+        let resumed = resume_battle(history[8].clone(), 8, Some((40, 35)), &odds);
+        assert_eq!(resumed.len(), odds.round_count - 8 + 1);
+        let finished = history.last().unwrap();
+        let resumed = resumed.last().unwrap();
+        assert_close(
+            resumed.get_odds_of_attacker_win(),
+            finished.get_odds_of_attacker_win(),
+        );
+
+        let uncertain = WarWeights::uniform(40..=50, 30..=30);
+        assert_close(uncertain.get(45, 30), 1. / 11.);
+        let resumed = resume_battle(uncertain, 15, None, &odds);
+        assert_eq!(resumed.len(), 6);
+        assert_close(
+            resumed.last().unwrap().total() + resumed.last().unwrap().get_discarded_mass(),
+            1.,
+        );
+    }
+
+    #[test]
+    fn test_reinforcements() {
+        let odds = WarOdds::default();
+        let reinforced = WarOdds {
+            reinforcements: vec![Reinforcement {
+                side: Side::Defender,
+                soldiers: 15,
+                round: 4,
+            }],
+            ..odds.clone()
+        };
+        let history = calculate_history(30., 20., &reinforced);
+        let mut expected = calculate_history(30., 20., &odds)[3].clone();
+        expected.grow(30, 35);
+        expected.reinforce(Side::Defender, 15);
+        let expected = resume_battle(expected, 3, Some((30, 20)), &odds);
+        assert_eq!(history.len(), expected.len() + 3);
+        let (finished, expected) = (history.last().unwrap(), expected.last().unwrap());
+        assert_eq!(finished.max_defenders, 35);
+        assert_close(
+            finished.get_odds_of_defender_win(),
+            expected.get_odds_of_defender_win(),
+        );
+
+        // Battles that are already over stay over, and arrivals are capped at
+        // the grid size.
+        let mut weights = WarWeights::new(5, 4);
+        weights.add(5, 0, 0.5);
+        weights.add(3, 2, 0.5);
+        weights.reinforce(Side::Defender, 4);
+        assert_eq!(weights.get(5, 0), 0.5);
+        assert_eq!(weights.get(3, 4), 0.5);
+        assert_eq!(weights.max_defenders, 4);
+    }
+
+    #[test]
+    fn test_retreats() {
+        let odds = WarOdds {
+            attacker_retreat: Some(RetreatThreshold::Percent(50.)),
+            defender_retreat: Some(RetreatThreshold::Soldiers(10)),
+            ..WarOdds::default()
+        };
+        let weights = calculate_weights(40., 30., &odds);
+        assert!(weights
+            .iter()
+            .all(|(attackers, defenders, _)| attackers == 0
+                || defenders == 0
+                || (attackers >= 20 && defenders >= 10)));
+        let attacker_retreats = weights.get_retreating_results(Side::Attacker);
+        assert!(attacker_retreats
+            .iter()
+            .skip(20)
+            .all(|chance| *chance == 0.));
+        let defender_retreats = weights.get_retreating_results(Side::Defender);
+        assert!(defender_retreats
+            .iter()
+            .skip(10)
+            .all(|chance| *chance == 0.));
+        assert!(weights.get_odds_of_retreat(Side::Defender) > 0.);
+        let accounted = weights.total()
+            + weights.get_odds_of_retreat(Side::Attacker)
+            + weights.get_odds_of_retreat(Side::Defender)
+            + weights.get_discarded_mass();
+        assert!((accounted - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_resumed_retreats_use_starting_strength() {
+        let odds = WarOdds {
+            attacker_retreat: Some(RetreatThreshold::Percent(50.)),
+            ..WarOdds::default()
+        };
+        // Down to 60 of the 100 it started with, the attacker retreats below
+        // 50 rather than below half of what is left.
+        let history = resume_battle(WarWeights::starting(60, 60), 5, Some((100, 100)), &odds);
+        let retreats = history
+            .last()
+            .unwrap()
+            .get_retreating_results(Side::Attacker);
+        assert!(retreats.iter().skip(50).all(|chance| *chance == 0.));
+        assert!(retreats.iter().skip(30).sum::<f64>() > 0.);
+
+        let history = resume_battle(WarWeights::starting(60, 60), 5, None, &odds);
+        let retreats = history
+            .last()
+            .unwrap()
+            .get_retreating_results(Side::Attacker);
+        assert!(retreats.iter().skip(30).all(|chance| *chance == 0.));
+
+        let empty = WarWeights::new(10, 10);
+        assert!(empty.get_odds_of_retreat(Side::Attacker).is_sign_positive());
+        assert!(empty.get_odds_of_no_win().is_sign_positive());
+        assert_eq!(empty.get_expected_attackers(), None);
+    }
+
+    #[test]
+    fn test_volleys() {
+        let mut odds = WarOdds {
+            volley_rounds: 2,
+            round_count: 5,
+            ..WarOdds::default()
+        };
+        // Nobody has archers, so nobody shoots before the lines close.
+        assert_eq!(calculate_history(20., 50., &odds).len(), 6);
+
+        odds.set_toggle(Toggle::AttackerArchers, true);
+        let history = calculate_history(20., 50., &odds);
+        assert_eq!(history.len(), 8);
+        let volleyed = &history[1];
+        assert_close(volleyed.get_expected_attackers().unwrap(), 20.);
+        assert!((volleyed.get_expected_defenders().unwrap() - 48.).abs() < 1e-6);
+        let volleys = fire_volleys(&history[0], &odds);
+        assert_eq!(volleys.len(), 2);
+        assert_close(volleys[1].get(20, 46), history[2].get(20, 46));
+
+        // Rounds elapsed count the volleys, so resuming between them only
+        // fires the one left.
+        let resumed = resume_battle(history[1].clone(), 1, None, &odds);
+        assert_eq!(resumed.len(), history.len() - 1);
+        assert_close(resumed[1].get(20, 46), history[2].get(20, 46));
+        assert_close(
+            resumed.last().unwrap().get_odds_of_defender_win(),
+            history.last().unwrap().get_odds_of_defender_win(),
+        );
+
+        // The rest of the battle is fought from where the volleys left off.
+        let mut melee = odds.clone();
+        melee.volley_rounds = 0;
+        let expected = run_battle(volleys[1].clone(), &melee);
+        let weights = calculate_weights(20., 50., &odds);
+        assert_close(
+            weights.get_odds_of_defender_win(),
+            expected.get_odds_of_defender_win(),
+        );
+    }
+
+    #[test]
+    fn test_frontage() {
+        let odds = WarOdds {
+            frontage: Some(5),
+            kill_tolerance: 0.,
+            ..WarOdds::default()
+        };
+        let weights = step_battle(&WarWeights::starting(100, 20), &odds);
+        // No more than five soldiers a side can land a kill.
+        assert!(weights
+            .iter()
+            .all(|(attackers, defenders, _)| attackers >= 95 && defenders >= 15));
+        let untouched: f64 = weights
+            .iter()
+            .filter(|(_, defenders, _)| *defenders == 20)
+            .map(|(_, _, weight)| weight)
+            .sum();
+        assert_close(untouched, (1. - odds.get_attacker_rate()).powi(5));
+
+        // A frontage wider than both armies changes nothing.
+        let wide = WarOdds {
+            frontage: Some(40),
+            ..WarOdds::default()
+        };
+        let open = calculate_weights(40., 30., &WarOdds::default());
+        let weights = calculate_weights(40., 30., &wide);
+        for (attackers, defenders, weight) in open.iter() {
+            assert_eq!(weights.get(attackers, defenders), weight);
+        }
+    }
+}
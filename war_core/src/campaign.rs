@@ -0,0 +1,138 @@
+use crate::{run_battle, ArmySize, OutcomeStats, Side, WarOdds, WarWeights};
+
+// One battle in a campaign. The attackers are whoever survived the last one,
+// whether they won it outright or the defenders retreated.
+#[derive(Debug, Clone)]
+pub struct CampaignLeg {
+    pub defenders: usize,
+    pub odds: WarOdds,
+}
+
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub attackers: ArmySize,
+    pub legs: Vec<CampaignLeg>,
+}
+
+impl Campaign {
+    pub fn new(attackers: ArmySize) -> Self {
+        Self {
+            attackers,
+            legs: Vec::new(),
+        }
+    }
+
+    pub fn add_leg(mut self, defenders: usize, odds: WarOdds) -> Self {
+        self.legs.push(CampaignLeg { defenders, odds });
+        self
+    }
+}
+
+// The attackers' results after each leg, counting a defender retreat as a
+// win. Mass that lost, stalled, retreated itself or was wiped out along the
+// way has dropped out, so each leg's total is the chance of having carried
+// every leg up to and including it.
+#[derive(Debug, Clone)]
+pub struct CampaignReport {
+    pub legs: Vec<OutcomeStats>,
+}
+
+impl CampaignReport {
+    pub fn get_odds_of_winning_campaign(&self) -> f64 {
+        self.legs
+            .last()
+            .map(|leg| leg.get_win_probability())
+            .unwrap_or(0.)
+    }
+
+    // Surviving attackers at the end, given the whole campaign was won.
+    pub fn get_expected_strength(&self) -> Option<f64> {
+        self.legs.last()?.get_mean()
+    }
+
+    pub fn get_final_stats(&self) -> Option<&OutcomeStats> {
+        self.legs.last()
+    }
+}
+
+pub fn run_campaign(campaign: &Campaign) -> CampaignReport {
+    let mut survivors: Vec<(usize, f64)> = campaign.attackers.get_distribution();
+    let mut legs = Vec::new();
+    for leg in &campaign.legs {
+        let max_attackers = survivors.last().map(|(size, _)| *size).unwrap_or(0);
+        let mut weights = WarWeights::new(max_attackers, leg.defenders);
+        for (attackers, chance) in &survivors {
+            weights.add(*attackers, leg.defenders, *chance);
+        }
+        let results = get_carried_results(&run_battle(weights, &leg.odds));
+        survivors = results
+            .iter()
+            .enumerate()
+            .filter(|(_, chance)| **chance > 0.)
+            .map(|(attackers, chance)| (attackers, *chance))
+            .collect();
+        legs.push(OutcomeStats::from_results(results));
+    }
+    CampaignReport { legs }
+}
+
+// Chance of each number of attackers going on to the next leg: those left
+// after winning and those left when the defenders retreated.
+fn get_carried_results(weights: &WarWeights) -> Vec<f64> {
+    let mut results = weights.get_attackers_winning_results();
+    for (attackers, _, weight) in weights.iter_retreats(Side::Defender) {
+        results[attackers] += weight;
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_weights, RetreatThreshold};
+
+    #[test]
+    fn test_run_campaign() {
+        let camp = WarOdds {
+            defender_present: true,
+            defender_fortified: true,
+            defender_retreat: Some(RetreatThreshold::Percent(50.)),
+            ..WarOdds::default()
+        };
+        let city = WarOdds {
+            defender_city: true,
+            ..WarOdds::default()
+        };
+        let campaign = Campaign::new(ArmySize::Exact(60))
+            .add_leg(20, camp.clone())
+            .add_leg(15, city.clone());
+        let report = run_campaign(&campaign);
+        assert_eq!(report.legs.len(), 2);
+
+        // The camp's defenders give up at half strength, and the attackers
+        // march on from there as if they had won.
+        let first = calculate_weights(60., 20., &camp);
+        let retreated = first.get_odds_of_retreat(Side::Defender);
+        assert!(retreated > 0.);
+        assert!(
+            (report.legs[0].get_win_probability() - first.get_odds_of_attacker_win() - retreated)
+                .abs()
+                < 1e-12
+        );
+
+        let mut carried = first.get_attackers_winning_results();
+        for (attackers, _, chance) in first.iter_retreats(Side::Defender) {
+            carried[attackers] += chance;
+        }
+        let mut expected = 0.;
+        for (attackers, chance) in carried.iter().enumerate() {
+            if *chance > 0. {
+                expected += chance
+                    * calculate_weights(attackers as f64, 15., &city).get_odds_of_attacker_win();
+            }
+        }
+        assert!((report.get_odds_of_winning_campaign() - expected).abs() < 1e-9);
+        assert!(report.get_odds_of_winning_campaign() < report.legs[0].get_win_probability());
+        assert!(report.get_expected_strength().unwrap() < 60.);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{run_battle, Side, Toggle, WarOdds, WarWeights};
+
+// Chances for the toggles nobody is sure about. Any toggle not listed here
+// takes its value from the `WarOdds` it is used with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FogOfWar {
+    chances: Vec<(Toggle, f64)>,
+}
+
+impl FogOfWar {
+    pub fn get_chance(&self, odds: &WarOdds, toggle: Toggle) -> f64 {
+        self.chances
+            .iter()
+            .find(|(uncertain, _)| *uncertain == toggle)
+            .map(|(_, chance)| *chance)
+            .unwrap_or(if odds.get_toggle(toggle) { 1. } else { 0. })
+    }
+
+    // A certain chance goes straight into the odds. An uncertain one is kept
+    // here, and the odds get its more likely value, unless that would turn
+    // off a unit type the side is certain to have. That conflict is left for
+    // `is_consistent` to report.
+    pub fn set_chance(&mut self, odds: &mut WarOdds, toggle: Toggle, chance: f64) {
+        let chance = chance.clamp(0., 1.);
+        self.clear(toggle);
+        let uncertain = chance > 0. && chance < 1.;
+        let displaces_certain =
+            get_other_unit_toggle(toggle).is_some_and(|other| self.get_chance(odds, other) == 1.);
+        if !(uncertain && displaces_certain) {
+            odds.set_toggle(toggle, chance >= 0.5);
+        }
+        if uncertain {
+            self.chances.push((toggle, chance));
+        }
+    }
+
+    pub fn clear(&mut self, toggle: Toggle) {
+        self.chances.retain(|(uncertain, _)| *uncertain != toggle);
+    }
+
+    pub fn is_uncertain(&self) -> bool {
+        !self.chances.is_empty()
+    }
+
+    // Archers and elites on one side can't be had together, so they are one
+    // choice between archers, elites and neither, which only makes sense while
+    // their chances add up to at most one.
+    pub fn is_consistent(&self, odds: &WarOdds) -> bool {
+        [Side::Attacker, Side::Defender].into_iter().all(|side| {
+            let (archers, elites) = get_unit_toggles(side);
+            self.get_chance(odds, archers) + self.get_chance(odds, elites) <= 1. + 1e-9
+        })
+    }
+
+    // Every combination of the uncertain toggles with its probability. The
+    // unit type of each side is picked as one choice, see `is_consistent`;
+    // chances adding up to more than one are scaled down to fit.
+    pub fn get_scenarios(&self, odds: &WarOdds) -> Vec<(WarOdds, f64)> {
+        let mut choices: Vec<Choice> = Vec::new();
+        for (toggle, chance) in &self.chances {
+            if !is_unit_toggle(*toggle) {
+                choices.push(vec![
+                    (vec![(*toggle, true)], *chance),
+                    (vec![(*toggle, false)], 1. - chance),
+                ]);
+            }
+        }
+        for side in [Side::Attacker, Side::Defender] {
+            let (archers, elites) = get_unit_toggles(side);
+            if !self
+                .chances
+                .iter()
+                .any(|(toggle, _)| [archers, elites].contains(toggle))
+            {
+                continue;
+            }
+            let archer_chance = self.get_chance(odds, archers);
+            let elite_chance = self.get_chance(odds, elites);
+            let scale = (archer_chance + elite_chance).max(1.);
+            choices.push(vec![
+                (vec![(archers, true)], archer_chance / scale),
+                (vec![(elites, true)], elite_chance / scale),
+                (
+                    vec![(archers, false), (elites, false)],
+                    1. - (archer_chance + elite_chance) / scale,
+                ),
+            ]);
+        }
+
+        let mut scenarios = vec![(odds.clone(), 1.)];
+        for options in choices {
+            scenarios = scenarios
+                .iter()
+                .flat_map(|(scenario, weight)| {
+                    options.iter().map(move |(toggles, chance)| {
+                        let mut scenario = scenario.clone();
+                        for (toggle, value) in toggles {
+                            scenario.set_toggle(*toggle, *value);
+                        }
+                        (scenario, weight * chance)
+                    })
+                })
+                .filter(|(_, weight)| *weight > 0.)
+                .collect();
+        }
+        scenarios
+    }
+}
+
+// The options of one uncertain choice, each setting some toggles, with their
+// chances.
+type Choice = Vec<(Vec<(Toggle, bool)>, f64)>;
+
+fn get_unit_toggles(side: Side) -> (Toggle, Toggle) {
+    match side {
+        Side::Attacker => (Toggle::AttackerArchers, Toggle::AttackerElites),
+        Side::Defender => (Toggle::DefenderArchers, Toggle::DefenderElites),
+    }
+}
+
+fn is_unit_toggle(toggle: Toggle) -> bool {
+    get_other_unit_toggle(toggle).is_some()
+}
+
+// The other unit type on the same side, for archers and elites.
+fn get_other_unit_toggle(toggle: Toggle) -> Option<Toggle> {
+    [Side::Attacker, Side::Defender]
+        .into_iter()
+        .find_map(|side| match get_unit_toggles(side) {
+            (archers, elites) if toggle == archers => Some(elites),
+            (archers, elites) if toggle == elites => Some(archers),
+            _ => None,
+        })
+}
+
+// Blends per-round histories from several scenarios. A history that ended
+// early counts as staying at its final state for the remaining rounds.
+pub fn blend_histories(histories: &[(Vec<WarWeights>, f64)]) -> Vec<WarWeights> {
+    let rounds = histories
+        .iter()
+        .map(|(history, _)| history.len())
+        .max()
+        .unwrap_or(0);
+    (0..rounds)
+        .map(|round| {
+            let mut blended = WarWeights::new(0, 0);
+            for (history, weight) in histories {
+                let weights = history.get(round).or(history.last()).unwrap();
+                blended.add_weighted(weights, *weight);
+            }
+            blended
+        })
+        .collect()
+}
+
+pub fn run_fog_of_war(weights: WarWeights, odds: &WarOdds, fog: &FogOfWar) -> WarWeights {
+    let mut blended = WarWeights::new(weights.max_attackers, weights.max_defenders);
+    for (scenario, weight) in fog.get_scenarios(odds) {
+        blended.add_weighted(&run_battle(weights.clone(), &scenario), weight);
+    }
+    blended
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenarios() {
+        let mut odds = WarOdds::default();
+        let mut fog = FogOfWar::default();
+        fog.set_chance(&mut odds, Toggle::DefenderFortified, 0.25);
+        fog.set_chance(&mut odds, Toggle::DefenderArchers, 0.5);
+        fog.set_chance(&mut odds, Toggle::DefenderElites, 0.5);
+        fog.set_chance(&mut odds, Toggle::AttackerBlessed, 1.);
+        assert!(odds.attacker_blessed);
+        assert!(!odds.defender_fortified);
+        assert_eq!(fog.get_chance(&odds, Toggle::DefenderFortified), 0.25);
+
+        // Archers and elites are one choice, so each fortification comes
+        // with either of them but never both or neither.
+        let scenarios = fog.get_scenarios(&odds);
+        assert_eq!(scenarios.len(), 4);
+        assert!(scenarios
+            .iter()
+            .all(|(scenario, _)| scenario.defender_archers != scenario.defender_elites));
+        let total: f64 = scenarios.iter().map(|(_, weight)| weight).sum();
+        assert!((total - 1.).abs() < 1e-12);
+        let fortified: f64 = scenarios
+            .iter()
+            .filter(|(scenario, _)| scenario.defender_fortified)
+            .map(|(_, weight)| weight)
+            .sum();
+        assert!((fortified - 0.25).abs() < 1e-12);
+        assert!(fog.is_consistent(&odds));
+    }
+
+    #[test]
+    fn test_unit_types_keep_their_chances() {
+        let mut odds = WarOdds::default();
+        let mut fog = FogOfWar::default();
+        fog.set_chance(&mut odds, Toggle::AttackerArchers, 0.3);
+        fog.set_chance(&mut odds, Toggle::AttackerElites, 0.5);
+        let scenarios = fog.get_scenarios(&odds);
+        assert_eq!(scenarios.len(), 3);
+        let chance = |toggle| -> f64 {
+            scenarios
+                .iter()
+                .filter(|(scenario, _)| scenario.get_toggle(toggle))
+                .map(|(_, weight)| weight)
+                .sum()
+        };
+        assert!((chance(Toggle::AttackerArchers) - 0.3).abs() < 1e-12);
+        assert!((chance(Toggle::AttackerElites) - 0.5).abs() < 1e-12);
+        assert!(fog.is_consistent(&odds));
+
+        // Certain archers leave no room for a chance of elites, and stay on
+        // whatever chance of elites is given.
+        fog.set_chance(&mut odds, Toggle::AttackerArchers, 1.);
+        assert!(!fog.is_consistent(&odds));
+        fog.set_chance(&mut odds, Toggle::AttackerElites, 0.7);
+        assert!(odds.attacker_archers);
+        assert_eq!(fog.get_chance(&odds, Toggle::AttackerArchers), 1.);
+        assert!(!fog.is_consistent(&odds));
+        fog.set_chance(&mut odds, Toggle::AttackerArchers, 0.6);
+        fog.set_chance(&mut odds, Toggle::AttackerElites, 0.6);
+        assert!(!fog.is_consistent(&odds));
+        let total: f64 = fog
+            .get_scenarios(&odds)
+            .iter()
+            .map(|(_, weight)| weight)
+            .sum();
+        assert!((total - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fog_of_war_blends_scenarios() {
+        let mut odds = WarOdds::default();
+        let mut fog = FogOfWar::default();
+        fog.set_chance(&mut odds, Toggle::DefenderBlessed, 0.3);
+        let blended = run_fog_of_war(WarWeights::starting(40, 40), &odds, &fog);
+
+        let blessed = run_battle(
+            WarWeights::starting(40, 40),
+            &WarOdds {
+                defender_blessed: true,
+                ..odds.clone()
+            },
+        );
+        let unblessed = run_battle(
+            WarWeights::starting(40, 40),
+            &WarOdds {
+                defender_blessed: false,
+                ..odds.clone()
+            },
+        );
+        let expected =
+            0.3 * blessed.get_odds_of_attacker_win() + 0.7 * unblessed.get_odds_of_attacker_win();
+        assert!((blended.get_odds_of_attacker_win() - expected).abs() < 1e-12);
+        assert!((blended.total() + blended.get_discarded_mass() - 1.).abs() < 1e-9);
+    }
+}
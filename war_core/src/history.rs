@@ -0,0 +1,141 @@
+use crate::{Side, WarWeights};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundSummary {
+    pub round: usize,
+    pub attackers_won: f64,
+    pub defenders_won: f64,
+    pub mutual_destruction: f64,
+    pub attackers_retreated: f64,
+    pub defenders_retreated: f64,
+    pub expected_attackers: Option<f64>,
+    pub expected_defenders: Option<f64>,
+}
+
+impl RoundSummary {
+    pub fn from_weights(round: usize, weights: &WarWeights) -> Self {
+        Self {
+            round,
+            attackers_won: weights.get_odds_of_attacker_win(),
+            defenders_won: weights.get_odds_of_defender_win(),
+            mutual_destruction: weights.get_odds_of_mutual_destruction(),
+            attackers_retreated: weights.get_odds_of_retreat(Side::Attacker),
+            defenders_retreated: weights.get_odds_of_retreat(Side::Defender),
+            expected_attackers: weights.get_expected_attackers(),
+            expected_defenders: weights.get_expected_defenders(),
+        }
+    }
+
+    pub fn get_odds_of_ending(&self) -> f64 {
+        self.attackers_won
+            + self.defenders_won
+            + self.mutual_destruction
+            + self.attackers_retreated
+            + self.defenders_retreated
+    }
+}
+
+// Chance of the battle ending in each round, indexed by round number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndingRounds {
+    pub attacker_wins: Vec<f64>,
+    pub defender_wins: Vec<f64>,
+    pub endings: Vec<f64>,
+}
+
+impl EndingRounds {
+    pub fn from_history(history: &[RoundSummary]) -> Self {
+        let differences = |value: fn(&RoundSummary) -> f64| {
+            let mut previous = 0.;
+            history
+                .iter()
+                .map(|summary| {
+                    let current = value(summary);
+                    let difference = (current - previous).max(0.);
+                    previous = current;
+                    difference
+                })
+                .collect::<Vec<_>>()
+        };
+        Self {
+            attacker_wins: differences(|summary| summary.attackers_won),
+            defender_wins: differences(|summary| summary.defenders_won),
+            endings: differences(RoundSummary::get_odds_of_ending),
+        }
+    }
+
+    pub fn get_odds_of_ending(&self) -> f64 {
+        self.endings.iter().sum()
+    }
+
+    // Mean ending round among the battles that end within the simulated rounds.
+    pub fn get_expected_round(&self) -> Option<f64> {
+        let total = self.get_odds_of_ending();
+        if total <= 0. {
+            return None;
+        }
+        let weighted: f64 = self
+            .endings
+            .iter()
+            .enumerate()
+            .map(|(round, chance)| round as f64 * chance)
+            .sum();
+        Some(weighted / total)
+    }
+
+    // Median ending round among the battles that end within the simulated rounds.
+    pub fn get_median_round(&self) -> Option<usize> {
+        let total = self.get_odds_of_ending();
+        if total <= 0. {
+            return None;
+        }
+        let mut cumulative = 0.;
+        self.endings.iter().position(|chance| {
+            cumulative += chance;
+            cumulative >= total / 2.
+        })
+    }
+}
+
+pub fn summarize_history(history: &[WarWeights]) -> Vec<RoundSummary> {
+    history
+        .iter()
+        .enumerate()
+        .map(|(round, weights)| RoundSummary::from_weights(round, weights))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_history, WarOdds};
+
+    #[test]
+    fn test_ending_rounds() {
+        let odds = WarOdds {
+            round_count: 40,
+            ..WarOdds::default()
+        };
+        let history = summarize_history(&calculate_history(30., 30., &odds));
+        let ending_rounds = EndingRounds::from_history(&history);
+        let last = history.last().unwrap();
+        let attacker_wins: f64 = ending_rounds.attacker_wins.iter().sum();
+        let defender_wins: f64 = ending_rounds.defender_wins.iter().sum();
+        assert!((attacker_wins - last.attackers_won).abs() < 1e-12);
+        assert!((defender_wins - last.defenders_won).abs() < 1e-12);
+        assert_eq!(ending_rounds.endings[0], 0.);
+
+        let expected = ending_rounds.get_expected_round().unwrap();
+        let median = ending_rounds.get_median_round().unwrap();
+        assert!(expected > 1. && expected < 40.);
+        assert!((median as f64 - expected).abs() < 5.);
+    }
+
+    #[test]
+    fn test_no_endings() {
+        let history = summarize_history(&[WarWeights::starting(10, 10)]);
+        let ending_rounds = EndingRounds::from_history(&history);
+        assert_eq!(ending_rounds.get_expected_round(), None);
+        assert_eq!(ending_rounds.get_median_round(), None);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::calculate::{fight_next_round_with, make_room_for_reinforcements, BattleKernels};
+use crate::{blend_histories, resume_battle, FogOfWar, Toggle, WarOdds, WarWeights};
+
+// How likely each modifier combination is once the casualties seen so far are
+// taken into account.
+#[derive(Debug, Clone)]
+pub struct Posterior {
+    pub scenarios: Vec<(WarOdds, f64)>,
+    // The first observed counts, which percentage retreat thresholds are
+    // measured against.
+    pub started_with: Option<(usize, usize)>,
+}
+
+impl Posterior {
+    pub fn get_chance(&self, toggle: Toggle) -> f64 {
+        self.scenarios
+            .iter()
+            .filter(|(odds, _)| odds.get_toggle(toggle))
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    // The rest of the battle from the last observed counts, blended over the
+    // posterior.
+    pub fn predict(
+        &self,
+        attackers: usize,
+        defenders: usize,
+        rounds_elapsed: usize,
+    ) -> Vec<WarWeights> {
+        let histories: Vec<(Vec<WarWeights>, f64)> = self
+            .scenarios
+            .iter()
+            .map(|(odds, weight)| {
+                let current = WarWeights::starting(attackers, defenders);
+                (
+                    resume_battle(current, rounds_elapsed, self.started_with, odds),
+                    *weight,
+                )
+            })
+            .collect();
+        blend_histories(&histories)
+    }
+}
+
+// Weighs each combination the prior allows by how likely it makes the
+// observed counts, given as (attackers, defenders) at the start and after
+// every round since, volley rounds included. Returns `None` if no combination
+// could have produced them. Likelihoods are worked out without pruning or
+// cutting the kill tails, so an unlikely observation only makes a
+// combination unlikely rather than ruling it out.
+pub fn infer_modifiers(
+    prior: &FogOfWar,
+    odds: &WarOdds,
+    observed: &[(usize, usize)],
+) -> Option<Posterior> {
+    // Likelihoods shrink fast over many rounds, so work with logarithms.
+    let log_weights: Vec<(WarOdds, f64)> = prior
+        .get_scenarios(odds)
+        .into_iter()
+        .map(|(scenario, weight)| {
+            let exact = WarOdds {
+                prune_epsilon: 0.,
+                kill_tolerance: 0.,
+                ..scenario.clone()
+            };
+            let mut kernels = BattleKernels::new(&exact);
+            if let Some(started_with) = observed.first() {
+                kernels.start_battle(*started_with, &exact);
+            }
+            let log_likelihood: f64 = observed
+                .windows(2)
+                .enumerate()
+                .map(|(index, pair)| {
+                    let (from, to) = (pair[0], pair[1]);
+                    let mut current = WarWeights::starting(from.0, from.1);
+                    make_room_for_reinforcements(&mut current, index, &exact);
+                    fight_next_round_with(&current, index, &exact, &mut kernels)
+                        .get(to.0, to.1)
+                        .ln()
+                })
+                .sum();
+            (scenario, weight.ln() + log_likelihood)
+        })
+        .collect();
+    let best = log_weights
+        .iter()
+        .map(|(_, log_weight)| *log_weight)
+        .fold(f64::NEG_INFINITY, f64::max);
+    if best == f64::NEG_INFINITY {
+        return None;
+    }
+    let mut scenarios: Vec<(WarOdds, f64)> = log_weights
+        .into_iter()
+        .map(|(scenario, log_weight)| (scenario, (log_weight - best).exp()))
+        .filter(|(_, weight)| *weight > 0.)
+        .collect();
+    let total: f64 = scenarios.iter().map(|(_, weight)| weight).sum();
+    for (_, weight) in &mut scenarios {
+        *weight /= total;
+    }
+    Some(Posterior {
+        scenarios,
+        started_with: observed.first().copied(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fire_volleys, step_battle};
+
+    #[test]
+    fn test_infer_modifiers() {
+        let mut odds = WarOdds::default();
+        let mut prior = FogOfWar::default();
+        prior.set_chance(&mut odds, Toggle::AttackerPresent, 0.5);
+        let observed = [(50, 50), (49, 42), (48, 33)];
+        let posterior = infer_modifiers(&prior, &odds, &observed).unwrap();
+
+        let likelihood = |present: bool| {
+            let odds = WarOdds {
+                attacker_present: present,
+                ..odds.clone()
+            };
+            observed
+                .windows(2)
+                .map(|pair| {
+                    step_battle(&WarWeights::starting(pair[0].0, pair[0].1), &odds)
+                        .get(pair[1].0, pair[1].1)
+                })
+                .product::<f64>()
+        };
+        let expected = likelihood(true) / (likelihood(true) + likelihood(false));
+        assert!((posterior.get_chance(Toggle::AttackerPresent) - expected).abs() < 1e-12);
+        // Heavy defender losses point to the commander being there.
+        assert!(expected > 0.5);
+
+        let prediction = posterior.predict(48, 33, 2);
+        assert_eq!(prediction.len(), odds.round_count - 2 + 1);
+
+        // Nothing can bring back the dead.
+        assert!(infer_modifiers(&prior, &odds, &[(50, 50), (51, 50)]).is_none());
+    }
+
+    #[test]
+    fn test_infer_through_volleys() {
+        let mut odds = WarOdds {
+            volley_rounds: 1,
+            ..WarOdds::default()
+        };
+        odds.set_toggle(Toggle::AttackerArchers, true);
+        let mut prior = FogOfWar::default();
+        prior.set_chance(&mut odds, Toggle::DefenderPresent, 0.5);
+        // Only the attackers shoot in the volley, so the first round cannot
+        // cost them anyone.
+        let observed = [(30, 30), (30, 27), (26, 24)];
+        let posterior = infer_modifiers(&prior, &odds, &observed).unwrap();
+
+        let likelihood = |present: bool| {
+            let odds = WarOdds {
+                defender_present: present,
+                ..odds.clone()
+            };
+            fire_volleys(&WarWeights::starting(30, 30), &odds)[0].get(30, 27)
+                * resume_battle(WarWeights::starting(30, 27), 1, None, &odds)[1].get(26, 24)
+        };
+        let expected = likelihood(true) / (likelihood(true) + likelihood(false));
+        assert!((posterior.get_chance(Toggle::DefenderPresent) - expected).abs() < 1e-12);
+    }
+
+    // Losses far out in the tail are unlikely under every combination, but
+    // none of them can be ruled out by it.
+    #[test]
+    fn test_tail_observations_stay_possible() {
+        let mut odds = WarOdds::default();
+        let mut prior = FogOfWar::default();
+        prior.set_chance(&mut odds, Toggle::AttackerPresent, 0.5);
+        let observed = [(50, 50), (50, 20)];
+        assert_eq!(
+            step_battle(&WarWeights::starting(50, 50), &odds).get(50, 20),
+            0.
+        );
+        let posterior = infer_modifiers(&prior, &odds, &observed).unwrap();
+        assert_eq!(posterior.scenarios.len(), 2);
+        let chance = posterior.get_chance(Toggle::AttackerPresent);
+        assert!(chance > 0.5 && chance < 1.);
+    }
+}
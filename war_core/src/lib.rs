@@ -0,0 +1,43 @@
+mod army;
+mod binomial;
+mod calculate;
+mod campaign;
+mod fog;
+mod history;
+mod inference;
+mod mixed;
+mod odds;
+mod policy;
+mod resolve;
+mod sensitivity;
+mod simulate;
+mod solver;
+mod stats;
+mod sweep;
+mod weights;
+
+pub use army::{calculate_mixture, ArmySize};
+pub use binomial::binomial_pmf;
+pub use calculate::{
+    calculate_history, calculate_weights, fire_volleys, resume_battle, run_battle, run_history,
+    step_battle,
+};
+pub use campaign::{run_campaign, Campaign, CampaignLeg, CampaignReport};
+pub use fog::{blend_histories, run_fog_of_war, FogOfWar};
+pub use history::{summarize_history, EndingRounds, RoundSummary};
+pub use inference::{infer_modifiers, Posterior};
+pub use mixed::{run_mixed_battle, run_mixed_history, Composition, MixedWeights};
+pub use odds::{
+    Bonus, Reinforcement, ResolutionOrder, RetreatThreshold, Side, Toggle, UnitType, WarOdds,
+};
+pub use policy::{solve_retreat_policy, PolicyObjective, RetreatPolicy};
+pub use resolve::{
+    calculate_until_resolved, run_until_resolved, solve_final_outcome, solve_final_outcome_from,
+    MAX_RESOLVE_ROUNDS,
+};
+pub use sensitivity::{analyze_sensitivity, Adjustment, Sensitivity};
+pub use simulate::{simulate_battles, Estimate, SeededRng, SimulationReport};
+pub use solver::{find_minimum_force, ForceTarget};
+pub use stats::OutcomeStats;
+pub use sweep::{sweep_matchups, Sweep, SweepRange};
+pub use weights::WarWeights;
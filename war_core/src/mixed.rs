@@ -0,0 +1,542 @@
+use std::collections::BTreeMap;
+
+use crate::{ResolutionOrder, Side, UnitType, WarOdds, WarWeights};
+
+// How many of each unit type one side has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Composition {
+    pub regulars: usize,
+    pub archers: usize,
+    pub elites: usize,
+}
+
+impl Composition {
+    pub fn new(regulars: usize, archers: usize, elites: usize) -> Self {
+        Self {
+            regulars,
+            archers,
+            elites,
+        }
+    }
+
+    pub fn get(&self, unit: UnitType) -> usize {
+        match unit {
+            UnitType::Regular => self.regulars,
+            UnitType::Archer => self.archers,
+            UnitType::Elite => self.elites,
+        }
+    }
+
+    pub fn set(&mut self, unit: UnitType, count: usize) {
+        match unit {
+            UnitType::Regular => self.regulars = count,
+            UnitType::Archer => self.archers = count,
+            UnitType::Elite => self.elites = count,
+        }
+    }
+
+    pub fn get_total(&self) -> usize {
+        self.regulars + self.archers + self.elites
+    }
+
+    // The unit type of the casualty after `kills` have already been taken,
+    // going down `targeting` one type at a time.
+    fn get_next_casualty(&self, kills: usize, targeting: [UnitType; 3]) -> Option<UnitType> {
+        let mut kills = kills;
+        for unit in targeting {
+            if kills < self.get(unit) {
+                return Some(unit);
+            }
+            kills -= self.get(unit);
+        }
+        None
+    }
+
+    // The first `count` soldiers in `targeting` order, which is who stands in
+    // the front line.
+    pub fn get_front(&self, count: usize, targeting: [UnitType; 3]) -> Self {
+        let behind = self.remove(count, targeting);
+        Self::new(
+            self.regulars - behind.regulars,
+            self.archers - behind.archers,
+            self.elites - behind.elites,
+        )
+    }
+
+    // What is left after `kills` casualties taken in `targeting` order.
+    pub fn remove(&self, kills: usize, targeting: [UnitType; 3]) -> Self {
+        let mut kills = kills;
+        let mut remaining = *self;
+        for unit in targeting {
+            let taken = kills.min(remaining.get(unit));
+            remaining.set(unit, remaining.get(unit) - taken);
+            kills -= taken;
+        }
+        remaining
+    }
+}
+
+// Like `WarWeights`, but every cell holds the full composition of both sides.
+#[derive(Debug, Clone, Default)]
+pub struct MixedWeights {
+    cells: BTreeMap<(Composition, Composition), f64>,
+    discarded: f64,
+    attacker_retreats: BTreeMap<(Composition, Composition), f64>,
+    defender_retreats: BTreeMap<(Composition, Composition), f64>,
+}
+
+impl MixedWeights {
+    pub fn starting(attackers: Composition, defenders: Composition) -> Self {
+        let mut weights = Self::default();
+        weights.add(attackers, defenders, 1.);
+        weights
+    }
+
+    fn empty_like(other: &MixedWeights) -> Self {
+        Self {
+            discarded: other.discarded,
+            attacker_retreats: other.attacker_retreats.clone(),
+            defender_retreats: other.defender_retreats.clone(),
+            ..Self::default()
+        }
+    }
+
+    pub fn add(&mut self, attackers: Composition, defenders: Composition, weight: f64) {
+        if weight == 0. {
+            return;
+        }
+        *self.cells.entry((attackers, defenders)).or_insert(0.) += weight;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Composition, Composition, f64)> + '_ {
+        self.cells
+            .iter()
+            .map(|((attackers, defenders), weight)| (*attackers, *defenders, *weight))
+    }
+
+    pub fn total(&self) -> f64 {
+        self.cells.values().fold(0., |sum, weight| sum + weight)
+    }
+
+    pub fn get_discarded_mass(&self) -> f64 {
+        self.discarded
+    }
+
+    fn prune(&mut self, epsilon: f64) {
+        let mut pruned = 0.;
+        self.cells.retain(|_, weight| {
+            if *weight < epsilon {
+                pruned += *weight;
+                false
+            } else {
+                true
+            }
+        });
+        self.discarded += pruned;
+    }
+
+    // Reinforcements have no unit type of their own, so they join as
+    // regulars.
+    fn reinforce(&mut self, side: Side, soldiers: usize) {
+        let cells = std::mem::take(&mut self.cells);
+        for ((mut attackers, mut defenders), weight) in cells {
+            if attackers.get_total() > 0 && defenders.get_total() > 0 {
+                match side {
+                    Side::Attacker => attackers.regulars += soldiers,
+                    Side::Defender => defenders.regulars += soldiers,
+                }
+            }
+            self.add(attackers, defenders, weight);
+        }
+    }
+
+    fn retreat_below(&mut self, attackers_below: usize, defenders_below: usize) {
+        if attackers_below == 0 && defenders_below == 0 {
+            return;
+        }
+        let cells = std::mem::take(&mut self.cells);
+        for ((attackers, defenders), weight) in cells {
+            let (attacker_total, defender_total) = (attackers.get_total(), defenders.get_total());
+            let retreats = if attacker_total == 0 || defender_total == 0 {
+                &mut self.cells
+            } else if attacker_total < attackers_below {
+                &mut self.attacker_retreats
+            } else if defender_total < defenders_below {
+                &mut self.defender_retreats
+            } else {
+                &mut self.cells
+            };
+            *retreats.entry((attackers, defenders)).or_insert(0.) += weight;
+        }
+    }
+
+    // Expected number of `unit` left on `side`, over the battles still in
+    // the grid, `None` once none are.
+    pub fn get_expected(&self, side: Side, unit: UnitType) -> Option<f64> {
+        let total = self.total();
+        if total <= 0. {
+            return None;
+        }
+        let sum: f64 = self
+            .iter()
+            .map(|(attackers, defenders, weight)| match side {
+                Side::Attacker => attackers.get(unit) as f64 * weight,
+                Side::Defender => defenders.get(unit) as f64 * weight,
+            })
+            .sum();
+        Some(sum / total)
+    }
+
+    // Collapses every composition to its soldier count, so the usual stats
+    // and charts work on mixed battles too.
+    pub fn get_totals(&self) -> WarWeights {
+        let totals = |attackers: &Composition, defenders: &Composition| {
+            (attackers.get_total(), defenders.get_total())
+        };
+        let all = self
+            .cells
+            .keys()
+            .chain(self.attacker_retreats.keys())
+            .chain(self.defender_retreats.keys());
+        let (max_attackers, max_defenders) = all.fold((0, 0), |(a, d), (attackers, defenders)| {
+            let (attackers, defenders) = totals(attackers, defenders);
+            (a.max(attackers), d.max(defenders))
+        });
+        let mut weights = WarWeights::new(max_attackers, max_defenders);
+        for ((attackers, defenders), weight) in &self.cells {
+            let (attackers, defenders) = totals(attackers, defenders);
+            weights.add(attackers, defenders, *weight);
+        }
+        for (side, retreats) in [
+            (Side::Attacker, &self.attacker_retreats),
+            (Side::Defender, &self.defender_retreats),
+        ] {
+            for ((attackers, defenders), weight) in retreats {
+                let (attackers, defenders) = totals(attackers, defenders);
+                weights.add_retreat(side, attackers, defenders, *weight);
+            }
+        }
+        weights.discard(self.discarded);
+        weights
+    }
+}
+
+// Chance of every total kill count when `shooters` fire at `targets`. Shots
+// go one at a time, regulars first, at whichever unit type the targeting
+// order has reached, so each shot uses the rate for that pairing.
+fn get_kill_chances(
+    shooters: Composition,
+    targets: Composition,
+    targeting: [UnitType; 3],
+    rate: impl Fn(UnitType, UnitType) -> f64,
+) -> Vec<f64> {
+    let target_count = targets.get_total();
+    let mut chances = vec![0.; target_count + 1];
+    chances[0] = 1.;
+    let mut max_kills = 0;
+    for shooter in UnitType::ALL {
+        let rates: Vec<f64> = (0..target_count)
+            .map(|kills| {
+                let target = targets.get_next_casualty(kills, targeting).unwrap();
+                rate(shooter, target).clamp(0., 1.)
+            })
+            .collect();
+        for _ in 0..shooters.get(shooter) {
+            max_kills = (max_kills + 1).min(target_count);
+            for kills in (0..max_kills).rev() {
+                let hit = chances[kills] * rates[kills];
+                chances[kills] -= hit;
+                chances[kills + 1] += hit;
+            }
+        }
+    }
+    chances
+}
+
+// Kill distributions for both sides, keyed by the compositions firing and
+// being fired on. States repeat from round to round, so most are reused.
+#[derive(Default)]
+struct MixedKernels {
+    attacker: BTreeMap<(Composition, Composition), Vec<f64>>,
+    defender: BTreeMap<(Composition, Composition), Vec<f64>>,
+    // Volleys only depend on how many archers shoot.
+    attacker_volley: BTreeMap<(usize, Composition), Vec<f64>>,
+    defender_volley: BTreeMap<(usize, Composition), Vec<f64>>,
+    retreat_below: (usize, usize),
+}
+
+impl MixedKernels {
+    fn get(
+        &mut self,
+        side: Side,
+        shooters: Composition,
+        targets: Composition,
+        odds: &WarOdds,
+    ) -> &[f64] {
+        let kernels = match side {
+            Side::Attacker => &mut self.attacker,
+            Side::Defender => &mut self.defender,
+        };
+        kernels.entry((shooters, targets)).or_insert_with(|| {
+            // With a narrow frontage only the soldiers the enemy reaches first
+            // get to fight.
+            let enemy = match side {
+                Side::Attacker => Side::Defender,
+                Side::Defender => Side::Attacker,
+            };
+            let engaged = shooters.get_front(
+                odds.get_engaged(shooters.get_total()),
+                odds.get_targeting(enemy),
+            );
+            get_kill_chances(
+                engaged,
+                targets,
+                odds.get_targeting(side),
+                |shooter, target| odds.get_unit_rate(side, shooter, target),
+            )
+        })
+    }
+
+    // Only the archers shoot in a volley round, each with the volley chance
+    // whatever it is aimed at.
+    fn get_volley(
+        &mut self,
+        side: Side,
+        shooters: Composition,
+        targets: Composition,
+        odds: &WarOdds,
+    ) -> &[f64] {
+        let kernels = match side {
+            Side::Attacker => &mut self.attacker_volley,
+            Side::Defender => &mut self.defender_volley,
+        };
+        kernels
+            .entry((shooters.archers, targets))
+            .or_insert_with(|| {
+                let archers = Composition::new(0, shooters.archers, 0);
+                let rate = odds.volley_chance / 100.;
+                get_kill_chances(archers, targets, odds.get_targeting(side), |_, _| rate)
+            })
+    }
+}
+
+fn mixed_volley(
+    weights: &MixedWeights,
+    side: Side,
+    odds: &WarOdds,
+    kernels: &mut MixedKernels,
+) -> MixedWeights {
+    let targeting = odds.get_targeting(side);
+    let mut new_weights = MixedWeights::empty_like(weights);
+    for (attackers, defenders, weight) in weights.iter() {
+        if attackers.get_total() == 0 || defenders.get_total() == 0 {
+            new_weights.add(attackers, defenders, weight);
+            continue;
+        }
+        let (shooters, targets) = match side {
+            Side::Attacker => (attackers, defenders),
+            Side::Defender => (defenders, attackers),
+        };
+        for (kills, chance) in kernels
+            .get(side, shooters, targets, odds)
+            .iter()
+            .enumerate()
+        {
+            let survivors = targets.remove(kills, targeting);
+            match side {
+                Side::Attacker => new_weights.add(attackers, survivors, weight * chance),
+                Side::Defender => new_weights.add(survivors, defenders, weight * chance),
+            }
+        }
+    }
+    new_weights.prune(odds.prune_epsilon);
+    new_weights
+}
+
+// Both sides fire at the strength they had at the start of the round, with
+// `get_kills` giving the kill distribution for the side firing.
+fn simultaneous_mixed_volley(
+    weights: &MixedWeights,
+    odds: &WarOdds,
+    mut get_kills: impl FnMut(Side, Composition, Composition) -> Vec<f64>,
+) -> MixedWeights {
+    let mut new_weights = MixedWeights::empty_like(weights);
+    for (attackers, defenders, weight) in weights.iter() {
+        if attackers.get_total() == 0 || defenders.get_total() == 0 {
+            new_weights.add(attackers, defenders, weight);
+            continue;
+        }
+        let attacker_kills = get_kills(Side::Attacker, attackers, defenders);
+        let defender_kills = get_kills(Side::Defender, defenders, attackers);
+        for (defender_losses, attacker_chance) in attacker_kills.iter().enumerate() {
+            let new_defenders = defenders.remove(defender_losses, odds.attacker_targeting);
+            for (attacker_losses, defender_chance) in defender_kills.iter().enumerate() {
+                let new_attackers = attackers.remove(attacker_losses, odds.defender_targeting);
+                new_weights.add(
+                    new_attackers,
+                    new_defenders,
+                    weight * attacker_chance * defender_chance,
+                );
+            }
+        }
+    }
+    new_weights.prune(odds.prune_epsilon);
+    new_weights
+}
+
+fn step_mixed_battle_with(
+    weights: &MixedWeights,
+    round: usize,
+    odds: &WarOdds,
+    kernels: &mut MixedKernels,
+) -> MixedWeights {
+    let mut weights = weights.clone();
+    for reinforcement in odds.get_arrivals(round) {
+        weights.reinforce(reinforcement.side, reinforcement.soldiers);
+    }
+    let mut new_weights = match odds.resolution_order {
+        ResolutionOrder::AttackerFirst => {
+            let new_weights = mixed_volley(&weights, Side::Attacker, odds, kernels);
+            mixed_volley(&new_weights, Side::Defender, odds, kernels)
+        }
+        ResolutionOrder::DefenderFirst => {
+            let new_weights = mixed_volley(&weights, Side::Defender, odds, kernels);
+            mixed_volley(&new_weights, Side::Attacker, odds, kernels)
+        }
+        ResolutionOrder::Simultaneous => {
+            simultaneous_mixed_volley(&weights, odds, |side, shooters, targets| {
+                kernels.get(side, shooters, targets, odds).to_vec()
+            })
+        }
+    };
+    let (attackers_below, defenders_below) = kernels.retreat_below;
+    new_weights.retreat_below(attackers_below, defenders_below);
+    new_weights
+}
+
+fn fire_mixed_volley(
+    weights: &MixedWeights,
+    odds: &WarOdds,
+    kernels: &mut MixedKernels,
+) -> MixedWeights {
+    let mut new_weights = simultaneous_mixed_volley(weights, odds, |side, shooters, targets| {
+        kernels.get_volley(side, shooters, targets, odds).to_vec()
+    });
+    let (attackers_below, defenders_below) = kernels.retreat_below;
+    new_weights.retreat_below(attackers_below, defenders_below);
+    new_weights
+}
+
+// The distribution at the start of a mixed battle and after every round,
+// volleys included.
+// Casualties on each side are taken in the order the other side targets.
+pub fn run_mixed_history(
+    attackers: Composition,
+    defenders: Composition,
+    odds: &WarOdds,
+) -> Vec<MixedWeights> {
+    let below = |side, starting| {
+        odds.get_retreat(side)
+            .map(|threshold| threshold.get_soldiers(starting))
+            .unwrap_or(0)
+    };
+    let mut kernels = MixedKernels {
+        retreat_below: (
+            below(Side::Attacker, attackers.get_total()),
+            below(Side::Defender, defenders.get_total()),
+        ),
+        ..MixedKernels::default()
+    };
+    let mut history = vec![MixedWeights::starting(attackers, defenders)];
+    if attackers.archers > 0 || defenders.archers > 0 {
+        for _ in 0..odds.volley_rounds {
+            let weights = fire_mixed_volley(history.last().unwrap(), odds, &mut kernels);
+            history.push(weights);
+        }
+    }
+    for round in 1..=odds.round_count {
+        let weights = step_mixed_battle_with(history.last().unwrap(), round, odds, &mut kernels);
+        history.push(weights);
+    }
+    history
+}
+
+pub fn run_mixed_battle(
+    attackers: Composition,
+    defenders: Composition,
+    odds: &WarOdds,
+) -> MixedWeights {
+    run_mixed_history(attackers, defenders, odds).pop().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_weights, Toggle};
+
+    #[test]
+    fn test_targeting_order() {
+        let army = Composition::new(3, 2, 1);
+        let targeting = [UnitType::Archer, UnitType::Regular, UnitType::Elite];
+        assert_eq!(army.remove(1, targeting), Composition::new(3, 1, 1));
+        assert_eq!(army.remove(4, targeting), Composition::new(1, 0, 1));
+        assert_eq!(army.remove(9, targeting), Composition::default());
+        assert_eq!(army.get_next_casualty(5, targeting), Some(UnitType::Elite));
+        assert_eq!(army.get_next_casualty(6, targeting), None);
+        assert_eq!(army.get_front(4, targeting), Composition::new(2, 2, 0));
+    }
+
+    // An army of a single unit type fights exactly like the plain battle with
+    // that type's toggle on.
+    #[test]
+    fn test_matches_single_unit_type() {
+        for order in [
+            ResolutionOrder::AttackerFirst,
+            ResolutionOrder::Simultaneous,
+        ] {
+            let mut odds = WarOdds {
+                round_count: 8,
+                volley_rounds: 2,
+                kill_tolerance: 0.,
+                prune_epsilon: 0.,
+                resolution_order: order,
+                ..WarOdds::default()
+            };
+            let mixed = run_mixed_battle(
+                Composition::new(0, 25, 0),
+                Composition::new(0, 0, 20),
+                &odds,
+            )
+            .get_totals();
+            odds.set_toggle(Toggle::AttackerArchers, true);
+            odds.set_toggle(Toggle::DefenderElites, true);
+            let plain = calculate_weights(25., 20., &odds);
+            for (attackers, defenders, weight) in plain.iter() {
+                assert!((mixed.get(attackers, defenders) - weight).abs() < 1e-12);
+            }
+            assert_eq!(mixed.cell_count(), plain.cell_count());
+        }
+    }
+
+    #[test]
+    fn test_casualties_follow_targeting() {
+        let odds = WarOdds {
+            base_chance: 5.,
+            round_count: 3,
+            ..WarOdds::default()
+        };
+        let history = run_mixed_history(
+            Composition::new(30, 0, 0),
+            Composition::new(10, 10, 10),
+            &odds,
+        );
+        let weights = history.last().unwrap();
+        // The defenders' archers are targeted last, and a couple of kills a
+        // round are very unlikely to get through twenty others in three.
+        let expected = |unit| weights.get_expected(Side::Defender, unit).unwrap();
+        assert!(expected(UnitType::Archer) > 9.99);
+        assert!(expected(UnitType::Elite) > 9.8);
+        assert!(expected(UnitType::Regular) < 8.);
+        assert!((weights.total() + weights.get_discarded_mass() - 1.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,537 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Attacker,
+    Defender,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionOrder {
+    AttackerFirst,
+    DefenderFirst,
+    Simultaneous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnitType {
+    Regular,
+    Archer,
+    Elite,
+}
+
+impl UnitType {
+    pub const ALL: [UnitType; 3] = [UnitType::Regular, UnitType::Archer, UnitType::Elite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitType::Regular => "Regulars",
+            UnitType::Archer => "Archers",
+            UnitType::Elite => "Elites",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    AttackerPresent,
+    DefenderPresent,
+    AttackerBlessed,
+    DefenderBlessed,
+    DefenderFortified,
+    AttackerClaimed,
+    DefenderClaimed,
+    AttackerCity,
+    DefenderCity,
+    AttackerArchers,
+    DefenderArchers,
+    AttackerElites,
+    DefenderElites,
+}
+
+impl Toggle {
+    pub const ALL: [Toggle; 13] = [
+        Toggle::AttackerPresent,
+        Toggle::DefenderPresent,
+        Toggle::AttackerBlessed,
+        Toggle::DefenderBlessed,
+        Toggle::DefenderFortified,
+        Toggle::AttackerClaimed,
+        Toggle::DefenderClaimed,
+        Toggle::AttackerCity,
+        Toggle::DefenderCity,
+        Toggle::AttackerArchers,
+        Toggle::DefenderArchers,
+        Toggle::AttackerElites,
+        Toggle::DefenderElites,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Toggle::AttackerPresent => "Attacker Commander Present",
+            Toggle::DefenderPresent => "Defender Commander Present",
+            Toggle::AttackerBlessed => "Attacker Blessed",
+            Toggle::DefenderBlessed => "Defender Blessed",
+            Toggle::DefenderFortified => "Defender Fortified",
+            Toggle::AttackerClaimed => "Attacker Claimed",
+            Toggle::DefenderClaimed => "Defender Claimed",
+            Toggle::AttackerCity => "Attacker City",
+            Toggle::DefenderCity => "Defender City",
+            Toggle::AttackerArchers => "Attacker Archers",
+            Toggle::DefenderArchers => "Defender Archers",
+            Toggle::AttackerElites => "Attacker Elites",
+            Toggle::DefenderElites => "Defender Elites",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bonus {
+    BaseChance,
+    CommanderBonus,
+    BlessingBonus,
+    FortifiedDefBonus,
+    ClaimedDefBonus,
+    CityDefBonus,
+    ArcherAttackMalus,
+    ArcherDefenseMalus,
+    EliteAttackBonus,
+    EliteDefenseBonus,
+}
+
+impl Bonus {
+    pub const ALL: [Bonus; 10] = [
+        Bonus::BaseChance,
+        Bonus::CommanderBonus,
+        Bonus::BlessingBonus,
+        Bonus::FortifiedDefBonus,
+        Bonus::ClaimedDefBonus,
+        Bonus::CityDefBonus,
+        Bonus::ArcherAttackMalus,
+        Bonus::ArcherDefenseMalus,
+        Bonus::EliteAttackBonus,
+        Bonus::EliteDefenseBonus,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bonus::BaseChance => "Base Chance",
+            Bonus::CommanderBonus => "Commander Attack Bonus",
+            Bonus::BlessingBonus => "Blessing Bonus",
+            Bonus::FortifiedDefBonus => "Fortified Def Bonus",
+            Bonus::ClaimedDefBonus => "Claimed Def Bonus",
+            Bonus::CityDefBonus => "City Def Bonus",
+            Bonus::ArcherAttackMalus => "Archer Attack Malus",
+            Bonus::ArcherDefenseMalus => "Archer Defense Malus",
+            Bonus::EliteAttackBonus => "Elite Attack Bonus",
+            Bonus::EliteDefenseBonus => "Elite Defense Bonus",
+        }
+    }
+}
+
+// Soldiers joining one side at the start of a round, counting rounds from
+// one. They only join battles that are still going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reinforcement {
+    pub side: Side,
+    pub soldiers: usize,
+    pub round: usize,
+}
+
+// When a side gives up: below a fixed number of soldiers, or below a
+// percentage of the strength it started the battle with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetreatThreshold {
+    Soldiers(usize),
+    Percent(f64),
+}
+
+impl RetreatThreshold {
+    // The side retreats once it has fewer soldiers than this.
+    pub fn get_soldiers(&self, starting: usize) -> usize {
+        match self {
+            RetreatThreshold::Soldiers(soldiers) => *soldiers,
+            RetreatThreshold::Percent(percent) => {
+                (starting as f64 * percent.max(0.) / 100.).ceil() as usize
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WarOdds {
+    pub base_chance: f64,
+    pub commander_bonus: f64,
+    pub blessing_bonus: f64,
+    pub fortified_def_bonus: f64,
+    pub claimed_def_bonus: f64,
+    pub city_def_bonus: f64,
+    pub archer_attack_malus: f64,
+    pub archer_defense_malus: f64,
+    pub elite_attack_bonus: f64,
+    pub elite_defense_bonus: f64,
+    pub attacker_present: bool,
+    pub defender_present: bool,
+    pub attacker_blessed: bool,
+    pub defender_blessed: bool,
+    pub defender_fortified: bool,
+    pub attacker_claimed: bool,
+    pub defender_claimed: bool,
+    pub attacker_city: bool,
+    pub defender_city: bool,
+    pub attacker_archers: bool,
+    pub defender_archers: bool,
+    pub attacker_elites: bool,
+    pub defender_elites: bool,
+    pub round_count: usize,
+    // Rounds of ranged fire before the lines close, in which only sides with
+    // archers shoot, each archer killing with `volley_chance` percent.
+    pub volley_rounds: usize,
+    pub volley_chance: f64,
+    // Most soldiers a side can bring into melee in one round, such as at a
+    // pass or a gate. None for open ground where everyone fights.
+    pub frontage: Option<usize>,
+    pub prune_epsilon: f64,
+    pub kill_tolerance: f64,
+    pub resolution_order: ResolutionOrder,
+    pub reinforcements: Vec<Reinforcement>,
+    pub attacker_retreat: Option<RetreatThreshold>,
+    pub defender_retreat: Option<RetreatThreshold>,
+    // The order in which each side picks off the other side's unit types in
+    // a mixed battle, so the defenders listed first are the first to fall to
+    // the attackers.
+    pub attacker_targeting: [UnitType; 3],
+    pub defender_targeting: [UnitType; 3],
+}
+
+impl Default for WarOdds {
+    fn default() -> Self {
+        WarOdds {
+            base_chance: 10.,
+            commander_bonus: 1.,
+            blessing_bonus: 2.,
+            fortified_def_bonus: 1.,
+            claimed_def_bonus: 1.,
+            city_def_bonus: 2.,
+            attacker_present: true,
+            defender_present: false,
+            attacker_blessed: true,
+            defender_blessed: true,
+            defender_fortified: false,
+            attacker_claimed: false,
+            defender_claimed: false,
+            attacker_city: false,
+            defender_city: false,
+            attacker_archers: false,
+            defender_archers: false,
+            attacker_elites: false,
+            defender_elites: false,
+            archer_attack_malus: 1.,
+            archer_defense_malus: 1.,
+            elite_attack_bonus: 1.,
+            elite_defense_bonus: 1.,
+            round_count: 20,
+            volley_rounds: 0,
+            volley_chance: 10.,
+            frontage: None,
+            prune_epsilon: 1e-12,
+            kill_tolerance: 1e-9,
+            resolution_order: ResolutionOrder::AttackerFirst,
+            reinforcements: Vec::new(),
+            attacker_retreat: None,
+            defender_retreat: None,
+            attacker_targeting: [UnitType::Regular, UnitType::Elite, UnitType::Archer],
+            defender_targeting: [UnitType::Regular, UnitType::Elite, UnitType::Archer],
+        }
+    }
+}
+
+impl WarOdds {
+    // Reinforcements due at the start of `round`. Anything scheduled for
+    // round zero arrives with the first round.
+    pub fn get_arrivals(&self, round: usize) -> impl Iterator<Item = &Reinforcement> + '_ {
+        self.reinforcements
+            .iter()
+            .filter(move |reinforcement| reinforcement.round.max(1) == round)
+    }
+
+    pub fn get_last_arrival(&self) -> usize {
+        self.reinforcements
+            .iter()
+            .map(|reinforcement| reinforcement.round.max(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn get_retreat(&self, side: Side) -> Option<RetreatThreshold> {
+        match side {
+            Side::Attacker => self.attacker_retreat,
+            Side::Defender => self.defender_retreat,
+        }
+    }
+
+    pub fn set_retreat(&mut self, side: Side, threshold: Option<RetreatThreshold>) {
+        match side {
+            Side::Attacker => self.attacker_retreat = threshold,
+            Side::Defender => self.defender_retreat = threshold,
+        }
+    }
+
+    // Whether a retreat threshold depends on the strength the side started
+    // with, so battles starting at different sizes retreat at different
+    // counts.
+    pub fn has_relative_retreat(&self) -> bool {
+        [self.attacker_retreat, self.defender_retreat]
+            .iter()
+            .any(|threshold| matches!(threshold, Some(RetreatThreshold::Percent(_))))
+    }
+
+    pub fn get_targeting(&self, side: Side) -> [UnitType; 3] {
+        match side {
+            Side::Attacker => self.attacker_targeting,
+            Side::Defender => self.defender_targeting,
+        }
+    }
+
+    pub fn set_targeting(&mut self, side: Side, targeting: [UnitType; 3]) {
+        match side {
+            Side::Attacker => self.attacker_targeting = targeting,
+            Side::Defender => self.defender_targeting = targeting,
+        }
+    }
+
+    pub fn has_archers(&self, side: Side) -> bool {
+        match side {
+            Side::Attacker => self.attacker_archers,
+            Side::Defender => self.defender_archers,
+        }
+    }
+
+    // Kill chance of each soldier on `side` in a volley, zero without archers.
+    pub fn get_volley_rate(&self, side: Side) -> f64 {
+        if self.has_archers(side) {
+            self.volley_chance / 100.
+        } else {
+            0.
+        }
+    }
+
+    // Volley rounds that actually happen, none if neither side has archers.
+    pub fn get_volley_rounds(&self) -> usize {
+        if self.has_archers(Side::Attacker) || self.has_archers(Side::Defender) {
+            self.volley_rounds
+        } else {
+            0
+        }
+    }
+
+    // Melee rounds among the first `rounds_elapsed`, which count the volley
+    // rounds as well.
+    pub fn get_melee_rounds_elapsed(&self, rounds_elapsed: usize) -> usize {
+        rounds_elapsed.saturating_sub(self.get_volley_rounds())
+    }
+
+    // How many of `soldiers` actually fight in a melee round.
+    pub fn get_engaged(&self, soldiers: usize) -> usize {
+        match self.frontage {
+            Some(frontage) => soldiers.min(frontage),
+            None => soldiers,
+        }
+    }
+
+    pub fn get_total_reinforcements(&self, side: Side) -> usize {
+        self.get_reinforcements_after(side, 0)
+    }
+
+    // Soldiers still due to `side` once `rounds_elapsed` rounds are over.
+    pub fn get_reinforcements_after(&self, side: Side, rounds_elapsed: usize) -> usize {
+        self.reinforcements
+            .iter()
+            .filter(|reinforcement| {
+                reinforcement.side == side && reinforcement.round.max(1) > rounds_elapsed
+            })
+            .map(|reinforcement| reinforcement.soldiers)
+            .sum()
+    }
+
+    pub fn get_toggle(&self, toggle: Toggle) -> bool {
+        match toggle {
+            Toggle::AttackerPresent => self.attacker_present,
+            Toggle::DefenderPresent => self.defender_present,
+            Toggle::AttackerBlessed => self.attacker_blessed,
+            Toggle::DefenderBlessed => self.defender_blessed,
+            Toggle::DefenderFortified => self.defender_fortified,
+            Toggle::AttackerClaimed => self.attacker_claimed,
+            Toggle::DefenderClaimed => self.defender_claimed,
+            Toggle::AttackerCity => self.attacker_city,
+            Toggle::DefenderCity => self.defender_city,
+            Toggle::AttackerArchers => self.attacker_archers,
+            Toggle::DefenderArchers => self.defender_archers,
+            Toggle::AttackerElites => self.attacker_elites,
+            Toggle::DefenderElites => self.defender_elites,
+        }
+    }
+
+    // Archers and elites cancel each other out, so turning one on turns the
+    // other off for that side.
+    pub fn set_toggle(&mut self, toggle: Toggle, value: bool) {
+        match toggle {
+            Toggle::AttackerPresent => self.attacker_present = value,
+            Toggle::DefenderPresent => self.defender_present = value,
+            Toggle::AttackerBlessed => self.attacker_blessed = value,
+            Toggle::DefenderBlessed => self.defender_blessed = value,
+            Toggle::DefenderFortified => self.defender_fortified = value,
+            Toggle::AttackerClaimed => self.attacker_claimed = value,
+            Toggle::DefenderClaimed => self.defender_claimed = value,
+            Toggle::AttackerCity => self.attacker_city = value,
+            Toggle::DefenderCity => self.defender_city = value,
+            Toggle::AttackerArchers => {
+                self.attacker_archers = value;
+                if value {
+                    self.attacker_elites = false;
+                }
+            }
+            Toggle::DefenderArchers => {
+                self.defender_archers = value;
+                if value {
+                    self.defender_elites = false;
+                }
+            }
+            Toggle::AttackerElites => {
+                self.attacker_elites = value;
+                if value {
+                    self.attacker_archers = false;
+                }
+            }
+            Toggle::DefenderElites => {
+                self.defender_elites = value;
+                if value {
+                    self.defender_archers = false;
+                }
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, toggle: Toggle) {
+        self.set_toggle(toggle, !self.get_toggle(toggle));
+    }
+
+    pub fn get_bonus(&self, bonus: Bonus) -> f64 {
+        match bonus {
+            Bonus::BaseChance => self.base_chance,
+            Bonus::CommanderBonus => self.commander_bonus,
+            Bonus::BlessingBonus => self.blessing_bonus,
+            Bonus::FortifiedDefBonus => self.fortified_def_bonus,
+            Bonus::ClaimedDefBonus => self.claimed_def_bonus,
+            Bonus::CityDefBonus => self.city_def_bonus,
+            Bonus::ArcherAttackMalus => self.archer_attack_malus,
+            Bonus::ArcherDefenseMalus => self.archer_defense_malus,
+            Bonus::EliteAttackBonus => self.elite_attack_bonus,
+            Bonus::EliteDefenseBonus => self.elite_defense_bonus,
+        }
+    }
+
+    pub fn set_bonus(&mut self, bonus: Bonus, value: f64) {
+        match bonus {
+            Bonus::BaseChance => self.base_chance = value,
+            Bonus::CommanderBonus => self.commander_bonus = value,
+            Bonus::BlessingBonus => self.blessing_bonus = value,
+            Bonus::FortifiedDefBonus => self.fortified_def_bonus = value,
+            Bonus::ClaimedDefBonus => self.claimed_def_bonus = value,
+            Bonus::CityDefBonus => self.city_def_bonus = value,
+            Bonus::ArcherAttackMalus => self.archer_attack_malus = value,
+            Bonus::ArcherDefenseMalus => self.archer_defense_malus = value,
+            Bonus::EliteAttackBonus => self.elite_attack_bonus = value,
+            Bonus::EliteDefenseBonus => self.elite_defense_bonus = value,
+        }
+    }
+
+    pub fn get_attacker_rate(&self) -> f64 {
+        let mut rate = self.get_base_rate(Side::Attacker);
+        if self.defender_archers {
+            rate += self.archer_defense_malus;
+        }
+        if self.attacker_archers {
+            rate -= self.archer_attack_malus;
+        }
+        if self.attacker_elites {
+            rate += self.elite_attack_bonus;
+        }
+        if self.defender_elites {
+            rate -= self.elite_defense_bonus;
+        }
+        rate / 100.
+    }
+
+    pub fn get_defender_rate(&self) -> f64 {
+        let mut rate = self.get_base_rate(Side::Defender);
+        if self.attacker_archers {
+            rate += self.archer_defense_malus;
+        }
+        if self.defender_archers {
+            rate -= self.archer_attack_malus;
+        }
+        if self.defender_elites {
+            rate += self.elite_attack_bonus;
+        }
+        if self.attacker_elites {
+            rate -= self.elite_defense_bonus;
+        }
+        rate / 100.
+    }
+
+    // Kill chance of `side` in percent from everything but the unit types.
+    fn get_base_rate(&self, side: Side) -> f64 {
+        let mut rate = self.base_chance;
+        match side {
+            Side::Attacker => {
+                if self.attacker_present {
+                    rate += self.commander_bonus;
+                }
+                if self.attacker_blessed {
+                    rate += self.blessing_bonus;
+                }
+                if self.defender_claimed {
+                    rate -= self.claimed_def_bonus;
+                }
+                if self.defender_present && self.defender_fortified {
+                    rate -= self.fortified_def_bonus;
+                }
+                if self.defender_city {
+                    rate -= self.city_def_bonus;
+                }
+            }
+            Side::Defender => {
+                if self.defender_present {
+                    rate += self.commander_bonus;
+                }
+                if self.defender_blessed {
+                    rate += self.blessing_bonus;
+                }
+                if self.attacker_claimed {
+                    rate -= self.claimed_def_bonus;
+                }
+                if self.attacker_city {
+                    rate -= self.city_def_bonus;
+                }
+            }
+        }
+        rate
+    }
+
+    // Chance that one `shooter` on `side` kills one `target` in a mixed
+    // battle. The archer and elite toggles are ignored, since each unit
+    // brings its own attack and defence.
+    pub fn get_unit_rate(&self, side: Side, shooter: UnitType, target: UnitType) -> f64 {
+        let mut rate = self.get_base_rate(side);
+        match shooter {
+            UnitType::Regular => {}
+            UnitType::Archer => rate -= self.archer_attack_malus,
+            UnitType::Elite => rate += self.elite_attack_bonus,
+        }
+        match target {
+            UnitType::Regular => {}
+            UnitType::Archer => rate += self.archer_defense_malus,
+            UnitType::Elite => rate -= self.elite_defense_bonus,
+        }
+        rate / 100.
+    }
+}
@@ -0,0 +1,333 @@
+use crate::calculate::{
+    back_up_archer_volley_with, back_up_arrivals, back_up_round_with, BattleKernels,
+};
+use crate::{Side, WarOdds};
+
+// What the side deciding whether to stay in the fight is trying to get.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyObjective {
+    // How far the strength difference has moved in the deciding side's
+    // favour since the start, which is the net kills without reinforcements.
+    NetKills,
+    // One for a win, less `loss_penalty` times the fraction of the starting
+    // army lost.
+    WinProbability { loss_penalty: f64 },
+}
+
+impl PolicyObjective {
+    fn get_value(
+        &self,
+        own: usize,
+        enemy: usize,
+        own_starting: usize,
+        enemy_starting: usize,
+    ) -> f64 {
+        match self {
+            PolicyObjective::NetKills => {
+                (own as f64 - enemy as f64) - (own_starting as f64 - enemy_starting as f64)
+            }
+            PolicyObjective::WinProbability { loss_penalty } => {
+                let won = if enemy == 0 && own > 0 { 1. } else { 0. };
+                let lost = own_starting.saturating_sub(own) as f64 / own_starting.max(1) as f64;
+                won - loss_penalty * lost
+            }
+        }
+    }
+}
+
+// Whether to keep fighting in every state, indexed by rounds already fought,
+// volley rounds first, and the soldiers left on each side. The grid leaves
+// room for every scheduled reinforcement.
+#[derive(Debug, Clone)]
+pub struct RetreatPolicy {
+    pub side: Side,
+    // The army sizes the policy was solved for, which percentage retreat
+    // thresholds and the objective are measured against.
+    pub starting_attackers: usize,
+    pub starting_defenders: usize,
+    pub max_attackers: usize,
+    pub max_defenders: usize,
+    values: Vec<Vec<f64>>,
+    continues: Vec<Vec<bool>>,
+}
+
+impl RetreatPolicy {
+    fn index(&self, attackers: usize, defenders: usize) -> Option<usize> {
+        if attackers > self.max_attackers || defenders > self.max_defenders {
+            return None;
+        }
+        Some(attackers * (self.max_defenders + 1) + defenders)
+    }
+
+    pub fn get_round_count(&self) -> usize {
+        self.values.len() - 1
+    }
+
+    // Expected objective from this state when the policy is followed, or
+    // None for states off the grid.
+    pub fn get_value(&self, attackers: usize, defenders: usize, round: usize) -> Option<f64> {
+        let cell = self.index(attackers, defenders)?;
+        Some(self.values[round.min(self.get_round_count())][cell])
+    }
+
+    // False once fighting on is worse than pulling out, and for battles that
+    // are already over or out of rounds. None for states off the grid.
+    pub fn should_continue(
+        &self,
+        attackers: usize,
+        defenders: usize,
+        round: usize,
+    ) -> Option<bool> {
+        let cell = self.index(attackers, defenders)?;
+        Some(round < self.get_round_count() && self.continues[round][cell])
+    }
+}
+
+// Backward induction over (attackers, defenders, round), from the last round
+// back to the first volley. Each round is backed up one side's fire at a time like
+// the sweep, and a battle either side's retreat threshold ends is worth the
+// objective of the state it ends in.
+pub fn solve_retreat_policy(
+    starting_attackers: usize,
+    starting_defenders: usize,
+    side: Side,
+    objective: PolicyObjective,
+    odds: &WarOdds,
+) -> RetreatPolicy {
+    let max_attackers = starting_attackers + odds.get_total_reinforcements(Side::Attacker);
+    let max_defenders = starting_defenders + odds.get_total_reinforcements(Side::Defender);
+    let volley_rounds = odds.get_volley_rounds();
+    let round_count = volley_rounds + odds.round_count;
+    let width = max_defenders + 1;
+    let stop_value = |attackers: usize, defenders: usize| match side {
+        Side::Attacker => {
+            objective.get_value(attackers, defenders, starting_attackers, starting_defenders)
+        }
+        Side::Defender => {
+            objective.get_value(defenders, attackers, starting_defenders, starting_attackers)
+        }
+    };
+    let stops: Vec<f64> = (0..(max_attackers + 1) * width)
+        .map(|cell| stop_value(cell / width, cell % width))
+        .collect();
+
+    let mut kernels = BattleKernels::new(odds);
+    kernels.start_battle((starting_attackers, starting_defenders), odds);
+    let mut values = vec![stops.clone(); round_count + 1];
+    let mut continues = vec![vec![false; stops.len()]; round_count];
+    for elapsed in (0..round_count).rev() {
+        let fight_on = if elapsed < volley_rounds {
+            back_up_archer_volley_with(
+                &values[elapsed + 1],
+                max_defenders,
+                stop_value,
+                &mut kernels,
+            )
+        } else {
+            let round = odds.get_melee_rounds_elapsed(elapsed) + 1;
+            let fight_on = back_up_round_with(
+                &values[elapsed + 1],
+                max_defenders,
+                stop_value,
+                odds,
+                &mut kernels,
+            );
+            // Reinforcements join before the round is fought, so fighting on
+            // from a state means fighting on from where the arrivals move it.
+            back_up_arrivals(&fight_on, max_attackers, max_defenders, round, odds)
+        };
+        for (cell, fight_on) in fight_on.into_iter().enumerate() {
+            if cell / width == 0 || cell % width == 0 {
+                continue;
+            }
+            continues[elapsed][cell] = fight_on + 1e-12 >= stops[cell];
+            values[elapsed][cell] = fight_on.max(stops[cell]);
+        }
+    }
+
+    RetreatPolicy {
+        side,
+        starting_attackers,
+        starting_defenders,
+        max_attackers,
+        max_defenders,
+        values,
+        continues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate::fight_round_with;
+    use crate::{calculate_weights, Reinforcement, RetreatThreshold, Toggle, WarWeights};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_hopeless_and_safe_battles() {
+        let harmless = WarOdds {
+            base_chance: 0.,
+            attacker_present: false,
+            attacker_blessed: false,
+            defender_blessed: false,
+            ..WarOdds::default()
+        };
+        let policy =
+            solve_retreat_policy(10, 10, Side::Attacker, PolicyObjective::NetKills, &harmless);
+        // Neither side can kill, so there is nothing to gain and nothing to lose.
+        assert_eq!(policy.should_continue(10, 10, 0), Some(true));
+        assert_eq!(policy.get_value(10, 10, 0), Some(0.));
+
+        let one_sided = WarOdds {
+            base_chance: 0.,
+            defender_blessed: false,
+            ..WarOdds::default()
+        };
+        let policy = solve_retreat_policy(
+            10,
+            10,
+            Side::Defender,
+            PolicyObjective::NetKills,
+            &one_sided,
+        );
+        assert_eq!(policy.should_continue(10, 10, 0), Some(false));
+        assert_eq!(policy.get_value(10, 10, 0), Some(0.));
+        let policy = solve_retreat_policy(
+            10,
+            10,
+            Side::Attacker,
+            PolicyObjective::NetKills,
+            &one_sided,
+        );
+        assert_eq!(policy.should_continue(10, 10, 0), Some(true));
+        assert!(policy.get_value(10, 10, 0).unwrap() > 0.);
+        // Off the grid on either side, rather than reading another state.
+        assert_eq!(policy.get_value(11, 10, 0), None);
+        assert_eq!(policy.get_value(5, 11, 0), None);
+        assert_eq!(policy.should_continue(11, 5, 0), None);
+    }
+
+    // Expected objective at the end of a battle fought to the last round,
+    // counting battles that ended in a retreat where they stopped.
+    fn get_expected_objective(
+        weights: &WarWeights,
+        objective: PolicyObjective,
+        starting: (usize, usize),
+    ) -> f64 {
+        weights
+            .iter()
+            .chain(weights.iter_retreats(Side::Attacker))
+            .chain(weights.iter_retreats(Side::Defender))
+            .map(|(attackers, defenders, chance)| {
+                chance * objective.get_value(attackers, defenders, starting.0, starting.1)
+            })
+            .sum()
+    }
+
+    // Volley rounds come before the melee in the policy's rounds, as they do
+    // in the battle's history.
+    #[test]
+    fn test_volleys_come_first() {
+        let mut odds = WarOdds {
+            base_chance: 0.,
+            defender_blessed: false,
+            volley_rounds: 2,
+            round_count: 3,
+            ..WarOdds::default()
+        };
+        odds.set_toggle(Toggle::AttackerArchers, true);
+        let objective = PolicyObjective::NetKills;
+        let policy = solve_retreat_policy(15, 12, Side::Attacker, objective, &odds);
+        assert_eq!(policy.get_round_count(), 5);
+        for round in 0..5 {
+            assert_eq!(policy.should_continue(15, 12, round), Some(true));
+        }
+        let weights = calculate_weights(15., 12., &odds);
+        let expected = get_expected_objective(&weights, objective, (15, 12));
+        assert!((policy.get_value(15, 12, 0).unwrap() - expected).abs() < 1e-9);
+        let without_volleys = WarOdds {
+            volley_rounds: 0,
+            ..odds.clone()
+        };
+        let policy = solve_retreat_policy(15, 12, Side::Attacker, objective, &without_volleys);
+        assert!(policy.get_value(15, 12, 0).unwrap() < expected - 0.1);
+    }
+
+    // When the defenders cannot kill, the attackers never gain by pulling
+    // out, so the policy's value is just the battle's. The defenders' own
+    // retreat threshold ends battles early all the same.
+    #[test]
+    fn test_retreat_thresholds_end_the_battle() {
+        let mut odds = WarOdds {
+            base_chance: 0.,
+            defender_blessed: false,
+            ..WarOdds::default()
+        };
+        let objective = PolicyObjective::NetKills;
+        for threshold in [
+            RetreatThreshold::Soldiers(4),
+            RetreatThreshold::Percent(60.),
+        ] {
+            odds.defender_retreat = Some(threshold);
+            let policy = solve_retreat_policy(12, 10, Side::Attacker, objective, &odds);
+            assert_eq!(policy.should_continue(12, 10, 0), Some(true));
+            let weights = calculate_weights(12., 10., &odds);
+            assert!(weights.get_odds_of_retreat(Side::Defender) > 0.5);
+            let expected = get_expected_objective(&weights, objective, (12, 10));
+            assert!((policy.get_value(12, 10, 0).unwrap() - expected).abs() < 1e-9);
+        }
+    }
+
+    // Checks the solver against plain backward induction that steps every
+    // state in every round. Only states the battle can actually reach are
+    // compared, since the rest may be pushed off the grid by the arrivals.
+    #[test]
+    fn test_matches_brute_force() {
+        let odds = WarOdds {
+            base_chance: 30.,
+            round_count: 6,
+            reinforcements: vec![Reinforcement {
+                side: Side::Defender,
+                soldiers: 3,
+                round: 3,
+            }],
+            ..WarOdds::default()
+        };
+        let objective = PolicyObjective::WinProbability { loss_penalty: 0.5 };
+        let policy = solve_retreat_policy(6, 5, Side::Attacker, objective, &odds);
+        assert_eq!(policy.max_defenders, 8);
+
+        let stop = |attackers, defenders| objective.get_value(attackers, defenders, 6, 5);
+        let mut kernels = BattleKernels::new(&odds);
+        let mut values = BTreeMap::new();
+        for attackers in 0..=6 {
+            for defenders in 0..=8 {
+                values.insert((attackers, defenders), stop(attackers, defenders));
+            }
+        }
+        for round in (0..odds.round_count).rev() {
+            let mut previous = values.clone();
+            for attackers in 1..=6 {
+                for defenders in 1..=8 {
+                    let mut current = WarWeights::new(6, 8);
+                    current.add(attackers, defenders, 1.);
+                    let fight_on: f64 = fight_round_with(&current, round + 1, &odds, &mut kernels)
+                        .iter()
+                        .map(|(a, d, chance)| chance * values[&(a, d.min(8))])
+                        .sum();
+                    previous.insert(
+                        (attackers, defenders),
+                        fight_on.max(stop(attackers, defenders)),
+                    );
+                }
+            }
+            values = previous;
+            for attackers in 1..=6 {
+                for defenders in 1..=5 {
+                    let value = policy.get_value(attackers, defenders, round).unwrap();
+                    assert!((value - values[&(attackers, defenders)]).abs() < 1e-12);
+                }
+            }
+        }
+    }
+}
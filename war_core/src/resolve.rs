@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use crate::calculate::{
+    fight_from_each_start, fight_next_round_with, make_room_for_reinforcements, step_battle_with,
+    BattleKernels,
+};
+use crate::{Side, WarOdds, WarWeights};
+
+// Hard stop for battles that can never resolve, such as two sides with no
+// chance to kill.
+pub const MAX_RESOLVE_ROUNDS: usize = 1000;
+
+// Like `calculate_history`, but keeps stepping until less than `tolerance` of
+// the mass is still fighting instead of stopping after `round_count` rounds.
+pub fn calculate_until_resolved(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+    tolerance: f64,
+) -> Vec<WarWeights> {
+    run_until_resolved(
+        WarWeights::starting(starting_attackers as usize, starting_defenders as usize),
+        0,
+        None,
+        odds,
+        tolerance,
+    )
+}
+
+// Like `resume_battle`, the round counter carries on from `rounds_elapsed`,
+// so reinforcements that already arrived do not arrive again, and percentage
+// retreat thresholds are measured against `started_with`.
+pub fn run_until_resolved(
+    weights: WarWeights,
+    rounds_elapsed: usize,
+    started_with: Option<(usize, usize)>,
+    odds: &WarOdds,
+    tolerance: f64,
+) -> Vec<WarWeights> {
+    let mut kernels = BattleKernels::new(odds);
+    fight_from_each_start(weights, started_with, odds, |mut weights, started_with| {
+        kernels.start_battle(started_with, odds);
+        make_room_for_reinforcements(&mut weights, rounds_elapsed, odds);
+        let mut history = vec![weights];
+        while history.len() <= MAX_RESOLVE_ROUNDS
+            && history.last().unwrap().get_odds_of_no_win() >= tolerance
+        {
+            let elapsed = rounds_elapsed + history.len() - 1;
+            let weights =
+                fight_next_round_with(history.last().unwrap(), elapsed, odds, &mut kernels);
+            history.push(weights);
+        }
+        history
+    })
+}
+
+// The final outcome with no round limit, solved as an absorbing Markov chain.
+// A round either leaves a state where it is or moves it somewhere with fewer
+// soldiers in total, so states are settled from the largest armies down and
+// the chance of standing still is folded into the chance of moving on.
+pub fn solve_final_outcome(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+) -> WarWeights {
+    solve_final_outcome_from(
+        &WarWeights::starting(starting_attackers as usize, starting_defenders as usize),
+        0,
+        None,
+        odds,
+    )
+}
+
+pub fn solve_final_outcome_from(
+    weights: &WarWeights,
+    rounds_elapsed: usize,
+    started_with: Option<(usize, usize)>,
+    odds: &WarOdds,
+) -> WarWeights {
+    let mut kernels = BattleKernels::new(odds);
+    fight_from_each_start(
+        weights.clone(),
+        started_with,
+        odds,
+        |weights, started_with| {
+            kernels.start_battle(started_with, odds);
+            vec![solve_from_start(
+                &weights,
+                rounds_elapsed,
+                odds,
+                &mut kernels,
+            )]
+        },
+    )
+    .pop()
+    .unwrap()
+}
+
+fn solve_from_start(
+    weights: &WarWeights,
+    rounds_elapsed: usize,
+    odds: &WarOdds,
+    kernels: &mut BattleKernels,
+) -> WarWeights {
+    // Volleys and reinforcements make the early rounds differ, so fight
+    // whatever is left of those out first and solve from once the last of them
+    // is over.
+    let mut weights = weights.clone();
+    make_room_for_reinforcements(&mut weights, rounds_elapsed, odds);
+    for elapsed in rounds_elapsed..odds.get_volley_rounds() + odds.get_last_arrival() {
+        weights = fight_next_round_with(&weights, elapsed, odds, kernels);
+    }
+    let mut outcome = WarWeights::empty_like(&weights);
+    let mut pending = BTreeMap::new();
+    for (attackers, defenders, weight) in weights.iter() {
+        pending.insert((attackers + defenders, attackers, defenders), weight);
+    }
+
+    while let Some(((_, attackers, defenders), mass)) = pending.pop_last() {
+        if mass < odds.prune_epsilon {
+            outcome.discard(mass);
+            continue;
+        }
+        if attackers == 0 || defenders == 0 {
+            outcome.add(attackers, defenders, mass);
+            continue;
+        }
+        let mut cell = WarWeights::new(outcome.max_attackers, outcome.max_defenders);
+        cell.add(attackers, defenders, 1.);
+        let next = step_battle_with(&cell, odds, kernels);
+        let standing_still = next.get(attackers, defenders);
+        if standing_still >= 1. {
+            // Neither side can ever land a kill.
+            outcome.add(attackers, defenders, mass);
+            continue;
+        }
+        let moving_on = mass / (1. - standing_still);
+        outcome.discard(moving_on * next.get_discarded_mass());
+        for side in [Side::Attacker, Side::Defender] {
+            for (new_attackers, new_defenders, weight) in next.iter_retreats(side) {
+                outcome.add_retreat(side, new_attackers, new_defenders, moving_on * weight);
+            }
+        }
+        for (new_attackers, new_defenders, weight) in next.iter() {
+            if (new_attackers, new_defenders) != (attackers, defenders) {
+                *pending
+                    .entry((new_attackers + new_defenders, new_attackers, new_defenders))
+                    .or_insert(0.) += moving_on * weight;
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reinforcement, RetreatThreshold, Toggle};
+
+    #[test]
+    fn test_solve_single_soldiers() {
+        let odds = WarOdds {
+            base_chance: 50.,
+            attacker_present: false,
+            attacker_blessed: false,
+            defender_blessed: false,
+            kill_tolerance: 0.,
+            ..WarOdds::default()
+        };
+        // Each round the attacker wins half the time and loses a quarter of
+        // the time, so it takes two thirds of the battles in the end.
+        let outcome = solve_final_outcome(1., 1., &odds);
+        assert!((outcome.get(1, 0) - 2. / 3.).abs() < 1e-12);
+        assert!((outcome.get(0, 1) - 1. / 3.).abs() < 1e-12);
+        assert_eq!(outcome.get_odds_of_no_win(), 0.);
+    }
+
+    #[test]
+    fn test_solve_with_retreats() {
+        let odds = WarOdds {
+            defender_retreat: Some(RetreatThreshold::Percent(25.)),
+            ..WarOdds::default()
+        };
+        let outcome = solve_final_outcome(30., 30., &odds);
+        let resolved = calculate_until_resolved(30., 30., &odds, 1e-10);
+        let resolved = resolved.last().unwrap();
+        let retreats = outcome.get_odds_of_retreat(Side::Defender);
+        assert!(retreats > 0.);
+        assert!((retreats - resolved.get_odds_of_retreat(Side::Defender)).abs() < 1e-8);
+        assert_eq!(outcome.get_odds_of_no_win(), 0.);
+    }
+
+    #[test]
+    fn test_solve_matches_running_until_resolved() {
+        let odds = WarOdds::default();
+        let outcome = solve_final_outcome(25., 20., &odds);
+        let history = calculate_until_resolved(25., 20., &odds, 1e-10);
+        let resolved = history.last().unwrap();
+        assert!(resolved.get_odds_of_no_win() < 1e-10);
+        assert!(history.len() < MAX_RESOLVE_ROUNDS);
+        for (exact, stepped) in outcome
+            .get_attackers_winning_results()
+            .iter()
+            .zip(resolved.get_attackers_winning_results())
+        {
+            assert!((exact - stepped).abs() < 1e-8);
+        }
+        assert!(
+            (outcome.get_odds_of_defender_win() - resolved.get_odds_of_defender_win()).abs() < 1e-8
+        );
+    }
+
+    #[test]
+    fn test_resumed_battles_skip_past_volleys() {
+        let mut odds = WarOdds {
+            volley_rounds: 2,
+            ..WarOdds::default()
+        };
+        odds.set_toggle(Toggle::DefenderArchers, true);
+        let history = calculate_until_resolved(25., 20., &odds, 1e-8);
+        // One volley is still to come after the first.
+        let resumed = run_until_resolved(history[1].clone(), 1, None, &odds, 1e-8);
+        assert_eq!(resumed.len(), history.len() - 1);
+        let (finished, resumed) = (history.last().unwrap(), resumed.last().unwrap());
+        assert!(
+            (finished.get_odds_of_attacker_win() - resumed.get_odds_of_attacker_win()).abs()
+                < 1e-12
+        );
+        let solved = solve_final_outcome(25., 20., &odds);
+        let resumed = solve_final_outcome_from(&history[1], 1, None, &odds);
+        assert!(
+            (solved.get_odds_of_attacker_win() - resumed.get_odds_of_attacker_win()).abs() < 1e-9
+        );
+        assert!(
+            (solved.get_odds_of_attacker_win() - finished.get_odds_of_attacker_win()).abs() < 1e-6
+        );
+    }
+
+    #[test]
+    fn test_resumed_battles_skip_past_arrivals() {
+        let odds = WarOdds::default();
+        let reinforced = WarOdds {
+            reinforcements: vec![Reinforcement {
+                side: Side::Defender,
+                soldiers: 10,
+                round: 2,
+            }],
+            ..odds.clone()
+        };
+        let current = WarWeights::starting(30, 20);
+        // Five rounds in, the reinforcements have already joined.
+        let resumed = solve_final_outcome_from(&current, 5, None, &reinforced);
+        let plain = solve_final_outcome_from(&current, 5, None, &odds);
+        assert_eq!(resumed.max_defenders, 20);
+        assert_eq!(
+            resumed.get_odds_of_attacker_win(),
+            plain.get_odds_of_attacker_win()
+        );
+        let resumed = run_until_resolved(current.clone(), 5, None, &reinforced, 1e-6);
+        let plain = run_until_resolved(current.clone(), 5, None, &odds, 1e-6);
+        assert_eq!(resumed.len(), plain.len());
+        assert_eq!(
+            resumed.last().unwrap().get_odds_of_attacker_win(),
+            plain.last().unwrap().get_odds_of_attacker_win()
+        );
+
+        // One round in, they are still to come.
+        let resumed = solve_final_outcome_from(&current, 1, None, &reinforced);
+        assert_eq!(resumed.max_defenders, 30);
+        assert!(
+            resumed.get_odds_of_attacker_win() < plain.last().unwrap().get_odds_of_attacker_win()
+        );
+    }
+}
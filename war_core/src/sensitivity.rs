@@ -0,0 +1,110 @@
+use crate::{calculate_weights, Bonus, Toggle, WarOdds};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Adjustment {
+    Flip(Toggle),
+    Nudge(Bonus, f64),
+}
+
+impl Adjustment {
+    pub fn name(&self) -> String {
+        match self {
+            Adjustment::Flip(toggle) => format!("Flip {}", toggle.name()),
+            Adjustment::Nudge(bonus, amount) => format!("{} {:+}", bonus.name(), amount),
+        }
+    }
+
+    pub fn apply(&self, odds: &WarOdds) -> WarOdds {
+        let mut odds = odds.clone();
+        match self {
+            Adjustment::Flip(toggle) => odds.toggle(*toggle),
+            Adjustment::Nudge(bonus, amount) => {
+                odds.set_bonus(*bonus, odds.get_bonus(*bonus) + amount)
+            }
+        }
+        odds
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensitivity {
+    pub adjustment: Adjustment,
+    pub attacker_change: f64,
+    pub defender_change: f64,
+}
+
+impl Sensitivity {
+    pub fn get_impact(&self) -> f64 {
+        self.attacker_change.abs().max(self.defender_change.abs())
+    }
+}
+
+// How much each side's win probability moves when every toggle is flipped and
+// every bonus is nudged by one, most influential first.
+pub fn analyze_sensitivity(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+) -> Vec<Sensitivity> {
+    let baseline = calculate_weights(starting_attackers, starting_defenders, odds);
+    let attacker_baseline = baseline.get_odds_of_attacker_win();
+    let defender_baseline = baseline.get_odds_of_defender_win();
+
+    let adjustments = Toggle::ALL
+        .iter()
+        .map(|toggle| Adjustment::Flip(*toggle))
+        .chain(Bonus::ALL.iter().flat_map(|bonus| {
+            [
+                Adjustment::Nudge(*bonus, 1.),
+                Adjustment::Nudge(*bonus, -1.),
+            ]
+        }));
+    let mut sensitivities: Vec<Sensitivity> = adjustments
+        .map(|adjustment| {
+            let weights = calculate_weights(
+                starting_attackers,
+                starting_defenders,
+                &adjustment.apply(odds),
+            );
+            Sensitivity {
+                adjustment,
+                attacker_change: weights.get_odds_of_attacker_win() - attacker_baseline,
+                defender_change: weights.get_odds_of_defender_win() - defender_baseline,
+            }
+        })
+        .collect();
+    sensitivities.sort_by(|a, b| b.get_impact().total_cmp(&a.get_impact()));
+    sensitivities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_sensitivity() {
+        let odds = WarOdds::default();
+        let sensitivities = analyze_sensitivity(20., 20., &odds);
+        assert_eq!(
+            sensitivities.len(),
+            Toggle::ALL.len() + Bonus::ALL.len() * 2
+        );
+        for pair in sensitivities.windows(2) {
+            assert!(pair[0].get_impact() >= pair[1].get_impact());
+        }
+
+        // A fortification only counts with the defending commander present.
+        let fortified = sensitivities
+            .iter()
+            .find(|s| s.adjustment == Adjustment::Flip(Toggle::DefenderFortified))
+            .unwrap();
+        assert_eq!(fortified.attacker_change, 0.);
+
+        let blessing = sensitivities
+            .iter()
+            .find(|s| s.adjustment == Adjustment::Flip(Toggle::AttackerBlessed))
+            .unwrap();
+        assert!(blessing.attacker_change < 0.);
+        assert!(blessing.defender_change > 0.);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{ResolutionOrder, Side, WarOdds};
+
+// SplitMix64, so runs are reproducible from a seed without pulling in a
+// random number crate.
+#[derive(Debug, Clone)]
+pub struct SeededRng(u64);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn count_kills(&mut self, engagements: usize, rate: f64) -> usize {
+        (0..engagements).filter(|_| self.next_f64() < rate).count()
+    }
+}
+
+// A proportion with its 95% Wilson score interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Estimate {
+    pub fn from_counts(successes: usize, trials: usize) -> Self {
+        if trials == 0 {
+            return Self {
+                value: 0.,
+                low: 0.,
+                high: 1.,
+            };
+        }
+        let z: f64 = 1.96;
+        let n = trials as f64;
+        let p = successes as f64 / n;
+        let denominator = 1. + z * z / n;
+        let centre = (p + z * z / (2. * n)) / denominator;
+        let spread = z * (p * (1. - p) / n + z * z / (4. * n * n)).sqrt() / denominator;
+        Self {
+            value: p,
+            low: (centre - spread).max(0.),
+            high: (centre + spread).min(1.),
+        }
+    }
+}
+
+// Outcome counts from playing battles out one by one, indexed by survivors
+// like the results on `WarWeights`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub battles: usize,
+    pub attacker_wins: Vec<usize>,
+    pub defender_wins: Vec<usize>,
+    pub mutual_destructions: usize,
+    pub attacker_retreats: usize,
+    pub defender_retreats: usize,
+    pub unresolved: usize,
+}
+
+impl SimulationReport {
+    pub fn get_odds_of_attacker_win(&self) -> Estimate {
+        Estimate::from_counts(self.attacker_wins.iter().sum(), self.battles)
+    }
+
+    pub fn get_odds_of_defender_win(&self) -> Estimate {
+        Estimate::from_counts(self.defender_wins.iter().sum(), self.battles)
+    }
+
+    pub fn get_odds_of_retreat(&self, side: Side) -> Estimate {
+        match side {
+            Side::Attacker => Estimate::from_counts(self.attacker_retreats, self.battles),
+            Side::Defender => Estimate::from_counts(self.defender_retreats, self.battles),
+        }
+    }
+
+    pub fn get_odds_of_no_win(&self) -> Estimate {
+        Estimate::from_counts(self.unresolved, self.battles)
+    }
+
+    pub fn get_attackers_winning_results(&self) -> Vec<Estimate> {
+        self.attacker_wins
+            .iter()
+            .map(|count| Estimate::from_counts(*count, self.battles))
+            .collect()
+    }
+
+    pub fn get_defenders_winning_results(&self) -> Vec<Estimate> {
+        self.defender_wins
+            .iter()
+            .map(|count| Estimate::from_counts(*count, self.battles))
+            .collect()
+    }
+}
+
+// Plays a single battle with every soldier rolling on its own, following the
+// same rates and round order as `step_battle`. Also says which side, if any,
+// retreated.
+fn simulate_battle(
+    mut attackers: usize,
+    mut defenders: usize,
+    odds: &WarOdds,
+    rng: &mut SeededRng,
+) -> (usize, usize, Option<Side>) {
+    let attacker_rate = odds.get_attacker_rate();
+    let defender_rate = odds.get_defender_rate();
+    let below = |side, starting| {
+        odds.get_retreat(side)
+            .map(|threshold| threshold.get_soldiers(starting))
+            .unwrap_or(0)
+    };
+    let attackers_below = below(Side::Attacker, attackers);
+    let defenders_below = below(Side::Defender, defenders);
+    let retreating = |attackers, defenders| match (attackers, defenders) {
+        (0, _) | (_, 0) => None,
+        _ if attackers < attackers_below => Some(Side::Attacker),
+        _ if defenders < defenders_below => Some(Side::Defender),
+        _ => None,
+    };
+    let attacker_volley_rate = odds.get_volley_rate(Side::Attacker);
+    let defender_volley_rate = odds.get_volley_rate(Side::Defender);
+    for _ in 0..odds.get_volley_rounds() {
+        if attackers == 0 || defenders == 0 {
+            break;
+        }
+        let attacker_kills = rng.count_kills(attackers, attacker_volley_rate);
+        let defender_kills = rng.count_kills(defenders, defender_volley_rate);
+        defenders = defenders.saturating_sub(attacker_kills);
+        attackers = attackers.saturating_sub(defender_kills);
+        if let Some(side) = retreating(attackers, defenders) {
+            return (attackers, defenders, Some(side));
+        }
+    }
+    for round in 1..=odds.round_count {
+        if attackers == 0 || defenders == 0 {
+            break;
+        }
+        for reinforcement in odds.get_arrivals(round) {
+            match reinforcement.side {
+                Side::Attacker => attackers += reinforcement.soldiers,
+                Side::Defender => defenders += reinforcement.soldiers,
+            }
+        }
+        match odds.resolution_order {
+            ResolutionOrder::AttackerFirst => {
+                let attacker_kills = rng.count_kills(odds.get_engaged(attackers), attacker_rate);
+                defenders = defenders.saturating_sub(attacker_kills);
+                if defenders > 0 {
+                    let defender_kills =
+                        rng.count_kills(odds.get_engaged(defenders), defender_rate);
+                    attackers = attackers.saturating_sub(defender_kills);
+                }
+            }
+            ResolutionOrder::DefenderFirst => {
+                let defender_kills = rng.count_kills(odds.get_engaged(defenders), defender_rate);
+                attackers = attackers.saturating_sub(defender_kills);
+                if attackers > 0 {
+                    let attacker_kills =
+                        rng.count_kills(odds.get_engaged(attackers), attacker_rate);
+                    defenders = defenders.saturating_sub(attacker_kills);
+                }
+            }
+            ResolutionOrder::Simultaneous => {
+                let attacker_kills = rng.count_kills(odds.get_engaged(attackers), attacker_rate);
+                let defender_kills = rng.count_kills(odds.get_engaged(defenders), defender_rate);
+                defenders = defenders.saturating_sub(attacker_kills);
+                attackers = attackers.saturating_sub(defender_kills);
+            }
+        }
+        if let Some(side) = retreating(attackers, defenders) {
+            return (attackers, defenders, Some(side));
+        }
+    }
+    (attackers, defenders, None)
+}
+
+pub fn simulate_battles(
+    starting_attackers: f64,
+    starting_defenders: f64,
+    odds: &WarOdds,
+    battles: usize,
+    seed: u64,
+) -> SimulationReport {
+    let starting_attackers = starting_attackers as usize;
+    let starting_defenders = starting_defenders as usize;
+    let mut rng = SeededRng::new(seed);
+    let mut report = SimulationReport {
+        battles,
+        attacker_wins: vec![
+            0;
+            starting_attackers + odds.get_total_reinforcements(Side::Attacker) + 1
+        ],
+        defender_wins: vec![
+            0;
+            starting_defenders + odds.get_total_reinforcements(Side::Defender) + 1
+        ],
+        mutual_destructions: 0,
+        attacker_retreats: 0,
+        defender_retreats: 0,
+        unresolved: 0,
+    };
+    for _ in 0..battles {
+        match simulate_battle(starting_attackers, starting_defenders, odds, &mut rng) {
+            (_, _, Some(Side::Attacker)) => report.attacker_retreats += 1,
+            (_, _, Some(Side::Defender)) => report.defender_retreats += 1,
+            (0, 0, None) => report.mutual_destructions += 1,
+            (attackers, 0, None) => report.attacker_wins[attackers] += 1,
+            (0, defenders, None) => report.defender_wins[defenders] += 1,
+            _ => report.unresolved += 1,
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_weights, Reinforcement, RetreatThreshold, Toggle};
+
+    #[test]
+    fn test_estimate_interval() {
+        let estimate = Estimate::from_counts(50, 100);
+        assert_eq!(estimate.value, 0.5);
+        assert!((estimate.low - 0.4038).abs() < 1e-3);
+        assert!((estimate.high - 0.5962).abs() < 1e-3);
+        let none = Estimate::from_counts(0, 100);
+        assert_eq!(none.low, 0.);
+        assert!(none.high > 0.);
+    }
+
+    #[test]
+    fn test_simulation_is_seeded() {
+        let odds = WarOdds::default();
+        let first = simulate_battles(20., 20., &odds, 200, 7);
+        let second = simulate_battles(20., 20., &odds, 200, 7);
+        assert_eq!(first, second);
+        assert_ne!(first, simulate_battles(20., 20., &odds, 200, 8));
+    }
+
+    // Plays random scenarios out and checks the exact engine lands inside the
+    // simulated spread. Four standard errors keeps this from flaking. Volleys,
+    // frontage, retreats and reinforcements each come up in some of them.
+    #[test]
+    fn test_simulation_matches_engine() {
+        let mut rng = SeededRng::new(2023);
+        let orders = [
+            ResolutionOrder::AttackerFirst,
+            ResolutionOrder::DefenderFirst,
+            ResolutionOrder::Simultaneous,
+        ];
+        for scenario in 0..12 {
+            let round_count = 5 + (rng.next_u64() % 20) as usize;
+            let mut odds = WarOdds {
+                base_chance: 5. + rng.next_f64() * 25.,
+                round_count,
+                resolution_order: orders[scenario % orders.len()],
+                volley_rounds: (rng.next_u64() % 3) as usize,
+                volley_chance: 5. + rng.next_f64() * 15.,
+                ..WarOdds::default()
+            };
+            for toggle in Toggle::ALL {
+                odds.set_toggle(toggle, rng.next_f64() < 0.5);
+            }
+            if rng.next_f64() < 0.5 {
+                odds.frontage = Some(3 + (rng.next_u64() % 15) as usize);
+            }
+            if rng.next_f64() < 0.5 {
+                odds.attacker_retreat = Some(RetreatThreshold::Percent(10. + rng.next_f64() * 40.));
+            }
+            if rng.next_f64() < 0.5 {
+                odds.defender_retreat =
+                    Some(RetreatThreshold::Soldiers((rng.next_u64() % 10) as usize));
+            }
+            if rng.next_f64() < 0.5 {
+                odds.reinforcements.push(Reinforcement {
+                    side: if rng.next_f64() < 0.5 {
+                        Side::Attacker
+                    } else {
+                        Side::Defender
+                    },
+                    soldiers: 1 + (rng.next_u64() % 15) as usize,
+                    round: 1 + (rng.next_u64() % round_count as u64) as usize,
+                });
+            }
+            let attackers = 5. + (rng.next_u64() % 40) as f64;
+            let defenders = 5. + (rng.next_u64() % 40) as f64;
+            let battles = 4000;
+
+            let exact = calculate_weights(attackers, defenders, &odds);
+            let report = simulate_battles(attackers, defenders, &odds, battles, scenario as u64);
+            for (exact, simulated) in [
+                (
+                    exact.get_odds_of_attacker_win(),
+                    report.get_odds_of_attacker_win(),
+                ),
+                (
+                    exact.get_odds_of_defender_win(),
+                    report.get_odds_of_defender_win(),
+                ),
+                (exact.get_odds_of_no_win(), report.get_odds_of_no_win()),
+                (
+                    exact.get_odds_of_retreat(Side::Attacker),
+                    report.get_odds_of_retreat(Side::Attacker),
+                ),
+                (
+                    exact.get_odds_of_retreat(Side::Defender),
+                    report.get_odds_of_retreat(Side::Defender),
+                ),
+            ] {
+                let error = (exact * (1. - exact) / battles as f64).sqrt().max(1e-3);
+                assert!(
+                    (simulated.value - exact).abs() < 4. * error,
+                    "scenario {}: simulated {} against exact {}",
+                    scenario,
+                    simulated.value,
+                    exact
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{calculate_weights, Side, WarOdds};
+
+// Settings for finding the smallest army that wins often enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForceTarget {
+    pub side: Side,
+    pub opposing_soldiers: usize,
+    pub win_probability: f64,
+    pub min_survivors: usize,
+    pub max_soldiers: usize,
+}
+
+impl ForceTarget {
+    fn chance_with(&self, soldiers: usize, odds: &WarOdds) -> f64 {
+        let (attackers, defenders) = match self.side {
+            Side::Attacker => (soldiers, self.opposing_soldiers),
+            Side::Defender => (self.opposing_soldiers, soldiers),
+        };
+        calculate_weights(attackers as f64, defenders as f64, odds)
+            .get_stats(self.side)
+            .get_win_probability_with_at_least(self.min_survivors.max(1))
+    }
+}
+
+// Binary searches for the smallest army on `target.side` that reaches the
+// target, assuming extra soldiers never make a win less likely. Returns the
+// army size and the chance it achieved, or `None` if even `max_soldiers` falls
+// short.
+pub fn find_minimum_force(target: &ForceTarget, odds: &WarOdds) -> Option<(usize, f64)> {
+    let mut high = target.max_soldiers.max(target.min_survivors).max(1);
+    let mut high_chance = target.chance_with(high, odds);
+    if high_chance < target.win_probability {
+        return None;
+    }
+    let mut low = target.min_survivors.max(1) - 1;
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        let chance = target.chance_with(middle, odds);
+        if chance >= target.win_probability {
+            high = middle;
+            high_chance = chance;
+        } else {
+            low = middle;
+        }
+    }
+    Some((high, high_chance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_minimum_force() {
+        let odds = WarOdds::default();
+        let target = ForceTarget {
+            side: Side::Attacker,
+            opposing_soldiers: 30,
+            win_probability: 0.9,
+            min_survivors: 1,
+            max_soldiers: 200,
+        };
+        let (attackers, chance) = find_minimum_force(&target, &odds).unwrap();
+        assert!(chance >= 0.9);
+        assert!(target.chance_with(attackers - 1, &odds) < 0.9);
+
+        let stronger = ForceTarget {
+            min_survivors: 20,
+            ..target.clone()
+        };
+        let (more_attackers, _) = find_minimum_force(&stronger, &odds).unwrap();
+        assert!(more_attackers > attackers);
+    }
+
+    #[test]
+    fn test_find_minimum_force_out_of_reach() {
+        let target = ForceTarget {
+            side: Side::Defender,
+            opposing_soldiers: 100,
+            win_probability: 0.99,
+            min_survivors: 1,
+            max_soldiers: 10,
+        };
+        assert_eq!(find_minimum_force(&target, &WarOdds::default()), None);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Side, WarWeights};
+
+// Summary of one side's winning results, indexed by surviving soldiers. All of
+// the statistics other than the win probability are conditional on that side
+// winning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutcomeStats {
+    results: Vec<f64>,
+    total: f64,
+}
+
+impl OutcomeStats {
+    pub fn from_results(results: Vec<f64>) -> Self {
+        let total = results.iter().sum();
+        Self { results, total }
+    }
+
+    pub fn get_results(&self) -> &[f64] {
+        &self.results
+    }
+
+    pub fn get_win_probability(&self) -> f64 {
+        self.total
+    }
+
+    pub fn get_win_probability_with_at_least(&self, survivors: usize) -> f64 {
+        self.results.iter().skip(survivors).sum()
+    }
+
+    pub fn get_mean(&self) -> Option<f64> {
+        if self.total <= 0. {
+            return None;
+        }
+        let weighted: f64 = self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(survivors, chance)| survivors as f64 * chance)
+            .sum();
+        Some(weighted / self.total)
+    }
+
+    pub fn get_variance(&self) -> Option<f64> {
+        let mean = self.get_mean()?;
+        let weighted: f64 = self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(survivors, chance)| (survivors as f64 - mean).powi(2) * chance)
+            .sum();
+        Some(weighted / self.total)
+    }
+
+    pub fn get_standard_deviation(&self) -> Option<f64> {
+        self.get_variance().map(f64::sqrt)
+    }
+
+    // Smallest survivor count with at least `quantile` of the winning mass at
+    // or below it.
+    pub fn get_quantile(&self, quantile: f64) -> Option<usize> {
+        if self.total <= 0. {
+            return None;
+        }
+        let target = quantile.clamp(0., 1.) * self.total;
+        let mut cumulative = 0.;
+        let found = self.results.iter().position(|chance| {
+            cumulative += chance;
+            *chance > 0. && cumulative >= target
+        });
+        // Rounding can leave the running sum a hair short of the total.
+        found.or_else(|| self.results.iter().rposition(|chance| *chance > 0.))
+    }
+
+    pub fn get_median(&self) -> Option<usize> {
+        self.get_quantile(0.5)
+    }
+
+    pub fn get_mode(&self) -> Option<usize> {
+        if self.total <= 0. {
+            return None;
+        }
+        self.results
+            .iter()
+            .enumerate()
+            .fold(
+                None,
+                |best: Option<(usize, f64)>, (survivors, chance)| match best {
+                    Some((_, best_chance)) if best_chance >= *chance => best,
+                    _ => Some((survivors, *chance)),
+                },
+            )
+            .map(|(survivors, _)| survivors)
+    }
+
+    // Equal-tailed interval holding `mass` of the winning outcomes.
+    pub fn get_credible_interval(&self, mass: f64) -> Option<(usize, usize)> {
+        let tail = (1. - mass.clamp(0., 1.)) / 2.;
+        Some((self.get_quantile(tail)?, self.get_quantile(1. - tail)?))
+    }
+
+    // First and last survivor counts whose chance is above `threshold`.
+    pub fn get_range_above(&self, threshold: f64) -> Option<(usize, usize)> {
+        let first = self.results.iter().position(|chance| *chance > threshold)?;
+        let last = self
+            .results
+            .iter()
+            .rposition(|chance| *chance > threshold)?;
+        Some((first, last))
+    }
+}
+
+impl WarWeights {
+    pub fn get_attacker_stats(&self) -> OutcomeStats {
+        OutcomeStats::from_results(self.get_attackers_winning_results())
+    }
+
+    pub fn get_defender_stats(&self) -> OutcomeStats {
+        OutcomeStats::from_results(self.get_defenders_winning_results())
+    }
+
+    pub fn get_stats(&self, side: Side) -> OutcomeStats {
+        match side {
+            Side::Attacker => self.get_attacker_stats(),
+            Side::Defender => self.get_defender_stats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(left: f64, right: f64) {
+        assert!(
+            (left - right).abs() < 1e-12,
+            "{} is not close to {}",
+            left,
+            right
+        );
+    }
+
+    #[test]
+    fn test_outcome_stats() {
+        let stats = OutcomeStats::from_results(vec![0., 0.1, 0.2, 0.3, 0.1, 0.]);
+        assert_close(stats.get_win_probability(), 0.7);
+        // (0.1 + 0.4 + 0.9 + 0.4) / 0.7
+        assert_close(stats.get_mean().unwrap(), 1.8 / 0.7);
+        // (0.1 + 0.8 + 2.7 + 1.6) / 0.7 - mean^2
+        assert_close(
+            stats.get_variance().unwrap(),
+            5.2 / 0.7 - (1.8f64 / 0.7).powi(2),
+        );
+        assert_eq!(stats.get_median(), Some(3));
+        assert_eq!(stats.get_quantile(0.), Some(1));
+        assert_eq!(stats.get_quantile(0.1), Some(1));
+        assert_eq!(stats.get_quantile(0.2), Some(2));
+        assert_eq!(stats.get_quantile(1.), Some(4));
+        assert_eq!(stats.get_mode(), Some(3));
+        assert_eq!(stats.get_credible_interval(0.8), Some((1, 4)));
+        assert_eq!(stats.get_range_above(0.15), Some((2, 3)));
+        assert_close(stats.get_win_probability_with_at_least(3), 0.4);
+    }
+
+    #[test]
+    fn test_outcome_stats_without_wins() {
+        let stats = OutcomeStats::from_results(vec![0.; 5]);
+        assert_eq!(stats.get_win_probability(), 0.);
+        assert_eq!(stats.get_mean(), None);
+        assert_eq!(stats.get_variance(), None);
+        assert_eq!(stats.get_median(), None);
+        assert_eq!(stats.get_mode(), None);
+        assert_eq!(stats.get_credible_interval(0.9), None);
+        assert_eq!(stats.get_range_above(0.01), None);
+    }
+
+    #[test]
+    fn test_war_weights_stats() {
+        let mut weights = WarWeights::new(3, 3);
+        weights.add(2, 0, 0.25);
+        weights.add(3, 0, 0.25);
+        weights.add(0, 1, 0.5);
+        let attacker_stats = weights.get_attacker_stats();
+        assert_eq!(attacker_stats.get_results(), &[0., 0., 0.25, 0.25]);
+        assert_close(attacker_stats.get_mean().unwrap(), 2.5);
+        assert_eq!(weights.get_defender_stats().get_mode(), Some(1));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::calculate::{
+    back_up_archer_volley_with, back_up_arrivals, back_up_round_with, run_battle_with,
+    BattleKernels,
+};
+use crate::{Side, WarOdds, WarWeights};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepRange {
+    pub min: usize,
+    pub max: usize,
+    pub step: usize,
+}
+
+impl SweepRange {
+    pub fn values(&self) -> Vec<usize> {
+        (self.min..=self.max).step_by(self.step.max(1)).collect()
+    }
+}
+
+// Attacker win probability for every matchup in the grid, indexed as
+// `attacker_wins[defender_index][attacker_index]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub attackers: Vec<usize>,
+    pub defenders: Vec<usize>,
+    pub attacker_wins: Vec<Vec<f64>>,
+}
+
+impl Sweep {
+    pub fn get(&self, attackers: usize, defenders: usize) -> Option<f64> {
+        let attacker_index = self.attackers.iter().position(|a| *a == attackers)?;
+        let defender_index = self.defenders.iter().position(|d| *d == defenders)?;
+        Some(self.attacker_wins[defender_index][attacker_index])
+    }
+}
+
+// Attacker win probability for every pair of army sizes. The chance of
+// winning from a state does not depend on how the battle got there, so one
+// pass from the last round back to the first over every state up to the
+// largest armies answers the whole grid at once. Percentage retreat
+// thresholds do depend on the starting sizes, so with those each matchup is
+// run on its own instead.
+pub fn sweep_matchups(attackers: SweepRange, defenders: SweepRange, odds: &WarOdds) -> Sweep {
+    let attackers = attackers.values();
+    let defenders = defenders.values();
+    let mut kernels = BattleKernels::new(odds);
+    let attacker_wins = if odds.has_relative_retreat() {
+        defenders
+            .iter()
+            .map(|defender_count| {
+                attackers
+                    .iter()
+                    .map(|attacker_count| {
+                        let weights = WarWeights::starting(*attacker_count, *defender_count);
+                        run_battle_with(weights, odds, &mut kernels).get_odds_of_attacker_win()
+                    })
+                    .collect()
+            })
+            .collect()
+    } else {
+        let max_attackers = attackers.iter().copied().max().unwrap_or(0)
+            + odds.get_total_reinforcements(Side::Attacker);
+        let max_defenders = defenders.iter().copied().max().unwrap_or(0)
+            + odds.get_total_reinforcements(Side::Defender);
+        let width = max_defenders + 1;
+        let won = |cell: usize| {
+            let (attackers, defenders) = (cell / width, cell % width);
+            if attackers > 0 && defenders == 0 {
+                1.
+            } else {
+                0.
+            }
+        };
+        let mut wins: Vec<f64> = (0..(max_attackers + 1) * width).map(won).collect();
+        // Fixed soldier counts are all that is left, and those ignore the
+        // starting sizes.
+        kernels.start_battle((max_attackers, max_defenders), odds);
+        for round in (1..=odds.round_count).rev() {
+            wins = back_up_round_with(&wins, max_defenders, |_, _| 0., odds, &mut kernels);
+            // Reinforcements join before the round is fought.
+            wins = back_up_arrivals(&wins, max_attackers, max_defenders, round, odds);
+        }
+        for _ in 0..odds.get_volley_rounds() {
+            wins = back_up_archer_volley_with(&wins, max_defenders, |_, _| 0., &mut kernels);
+        }
+        defenders
+            .iter()
+            .map(|defender_count| {
+                attackers
+                    .iter()
+                    .map(|attacker_count| wins[attacker_count * width + defender_count])
+                    .collect()
+            })
+            .collect()
+    };
+    Sweep {
+        attackers,
+        defenders,
+        attacker_wins,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_weights, Reinforcement, ResolutionOrder, RetreatThreshold, Toggle};
+
+    #[test]
+    fn test_sweep_matches_single_runs() {
+        let mut volleys = WarOdds {
+            volley_rounds: 2,
+            attacker_retreat: Some(RetreatThreshold::Soldiers(4)),
+            reinforcements: vec![Reinforcement {
+                side: Side::Defender,
+                soldiers: 6,
+                round: 3,
+            }],
+            ..WarOdds::default()
+        };
+        volleys.set_toggle(Toggle::DefenderArchers, true);
+        let relative = WarOdds {
+            defender_retreat: Some(RetreatThreshold::Percent(40.)),
+            ..WarOdds::default()
+        };
+        let simultaneous = WarOdds {
+            resolution_order: ResolutionOrder::Simultaneous,
+            frontage: Some(12),
+            ..WarOdds::default()
+        };
+        for odds in [WarOdds::default(), volleys, relative, simultaneous] {
+            let sweep = sweep_matchups(
+                SweepRange {
+                    min: 10,
+                    max: 30,
+                    step: 10,
+                },
+                SweepRange {
+                    min: 5,
+                    max: 25,
+                    step: 5,
+                },
+                &odds,
+            );
+            assert_eq!(sweep.attackers, vec![10, 20, 30]);
+            assert_eq!(sweep.defenders, vec![5, 10, 15, 20, 25]);
+            for attackers in &sweep.attackers {
+                for defenders in &sweep.defenders {
+                    let single = calculate_weights(*attackers as f64, *defenders as f64, &odds);
+                    let swept = sweep.get(*attackers, *defenders).unwrap();
+                    assert!((swept - single.get_odds_of_attacker_win()).abs() < 1e-9);
+                }
+            }
+            assert_eq!(sweep.get(15, 5), None);
+        }
+    }
+}
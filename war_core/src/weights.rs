@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use crate::{ArmySize, Side};
+
+// The weights for one attacker count, stored densely over the band of
+// defender counts that has been touched. The band grows in either direction
+// by at least its own length, so repeated growth stays cheap.
+#[derive(Debug, Clone, Default)]
+struct Row {
+    start: usize,
+    values: Vec<f64>,
+}
+
+impl Row {
+    fn get(&self, defenders: usize) -> f64 {
+        defenders
+            .checked_sub(self.start)
+            .and_then(|offset| self.values.get(offset))
+            .copied()
+            .unwrap_or(0.)
+    }
+
+    fn add(&mut self, defenders: usize, weight: f64) {
+        if self.values.is_empty() {
+            self.start = defenders;
+        } else if defenders < self.start {
+            let grow = (self.start - defenders)
+                .max(self.values.len())
+                .min(self.start);
+            self.values.splice(0..0, std::iter::repeat_n(0., grow));
+            self.start -= grow;
+        }
+        let offset = defenders - self.start;
+        if offset >= self.values.len() {
+            let len = (offset + 1).max(self.values.len() * 2);
+            self.values.resize(len, 0.);
+        }
+        self.values[offset] += weight;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight != 0.)
+            .map(|(offset, weight)| (self.start + offset, *weight))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WarWeights {
+    pub max_attackers: usize,
+    pub max_defenders: usize,
+    // Indexed by attacker count. Cells without mass hold zero.
+    rows: Vec<Row>,
+    discarded: f64,
+    // Battles that ended with one side pulling out, keyed by the counts on
+    // both sides when it did.
+    attacker_retreats: BTreeMap<(usize, usize), f64>,
+    defender_retreats: BTreeMap<(usize, usize), f64>,
+}
+
+impl WarWeights {
+    pub fn new(max_attackers: usize, max_defenders: usize) -> Self {
+        Self {
+            max_attackers,
+            max_defenders,
+            rows: Vec::new(),
+            discarded: 0.,
+            attacker_retreats: BTreeMap::new(),
+            defender_retreats: BTreeMap::new(),
+        }
+    }
+
+    // A battle that has not started yet, with all of the mass in one cell.
+    pub fn starting(attackers: usize, defenders: usize) -> Self {
+        let mut weights = Self::new(attackers, defenders);
+        weights.add(attackers, defenders, 1.);
+        weights
+    }
+
+    // Spreads the mass evenly over every combination in the two ranges.
+    pub fn uniform(attackers: RangeInclusive<usize>, defenders: RangeInclusive<usize>) -> Self {
+        Self::from_sizes(
+            &ArmySize::Uniform {
+                min: *attackers.start(),
+                max: *attackers.end(),
+            },
+            &ArmySize::Uniform {
+                min: *defenders.start(),
+                max: *defenders.end(),
+            },
+        )
+    }
+
+    // An empty grid of the same size that carries over the discarded mass and
+    // the battles that already ended in a retreat.
+    pub fn empty_like(other: &WarWeights) -> Self {
+        Self {
+            discarded: other.discarded,
+            attacker_retreats: other.attacker_retreats.clone(),
+            defender_retreats: other.defender_retreats.clone(),
+            ..Self::new(other.max_attackers, other.max_defenders)
+        }
+    }
+
+    pub fn get(&self, attackers: usize, defenders: usize) -> f64 {
+        self.rows
+            .get(attackers)
+            .map_or(0., |row| row.get(defenders))
+    }
+
+    pub fn add(&mut self, attackers: usize, defenders: usize, weight: f64) {
+        if weight == 0. {
+            return;
+        }
+        if attackers >= self.rows.len() {
+            self.rows.resize_with(attackers + 1, Row::default);
+        }
+        self.rows[attackers].add(defenders, weight);
+    }
+
+    // Folds in another distribution scaled by `weight`, growing the grid to
+    // fit both. Used to blend the results of several scenarios.
+    pub fn add_weighted(&mut self, other: &WarWeights, weight: f64) {
+        self.max_attackers = self.max_attackers.max(other.max_attackers);
+        self.max_defenders = self.max_defenders.max(other.max_defenders);
+        for (attackers, defenders, other_weight) in other.iter() {
+            self.add(attackers, defenders, other_weight * weight);
+        }
+        for side in [Side::Attacker, Side::Defender] {
+            for (attackers, defenders, other_weight) in other.iter_retreats(side) {
+                self.add_retreat(side, attackers, defenders, other_weight * weight);
+            }
+        }
+        self.discarded += other.discarded * weight;
+    }
+
+    // Widens the grid to at least these sizes, for example to leave room for
+    // reinforcements before the battle is fought.
+    pub fn grow(&mut self, max_attackers: usize, max_defenders: usize) {
+        self.max_attackers = self.max_attackers.max(max_attackers);
+        self.max_defenders = self.max_defenders.max(max_defenders);
+    }
+
+    // Adds soldiers to one side of every battle still going, capped at the
+    // grid size.
+    pub fn reinforce(&mut self, side: Side, soldiers: usize) {
+        if soldiers == 0 {
+            return;
+        }
+        let cells = std::mem::take(&mut self.rows);
+        let cells = cells.iter().enumerate().flat_map(|(attackers, row)| {
+            row.iter()
+                .map(move |(defenders, weight)| (attackers, defenders, weight))
+        });
+        for (attackers, defenders, weight) in cells {
+            let (attackers, defenders) = match side {
+                _ if attackers == 0 || defenders == 0 => (attackers, defenders),
+                Side::Attacker => ((attackers + soldiers).min(self.max_attackers), defenders),
+                Side::Defender => (attackers, (defenders + soldiers).min(self.max_defenders)),
+            };
+            self.add(attackers, defenders, weight);
+        }
+    }
+
+    fn get_retreats(&self, side: Side) -> &BTreeMap<(usize, usize), f64> {
+        match side {
+            Side::Attacker => &self.attacker_retreats,
+            Side::Defender => &self.defender_retreats,
+        }
+    }
+
+    pub fn add_retreat(&mut self, side: Side, attackers: usize, defenders: usize, weight: f64) {
+        if weight == 0. {
+            return;
+        }
+        let retreats = match side {
+            Side::Attacker => &mut self.attacker_retreats,
+            Side::Defender => &mut self.defender_retreats,
+        };
+        *retreats.entry((attackers, defenders)).or_insert(0.) += weight;
+    }
+
+    // Ends every battle still going where a side has fewer soldiers than its
+    // threshold. The attackers are the ones to call it off if both are.
+    pub fn retreat_below(&mut self, attackers_below: usize, defenders_below: usize) {
+        if attackers_below == 0 && defenders_below == 0 {
+            return;
+        }
+        let cells = std::mem::take(&mut self.rows);
+        let cells = cells.iter().enumerate().flat_map(|(attackers, row)| {
+            row.iter()
+                .map(move |(defenders, weight)| (attackers, defenders, weight))
+        });
+        for (attackers, defenders, weight) in cells {
+            if attackers == 0 || defenders == 0 {
+                self.add(attackers, defenders, weight);
+            } else if attackers < attackers_below {
+                self.add_retreat(Side::Attacker, attackers, defenders, weight);
+            } else if defenders < defenders_below {
+                self.add_retreat(Side::Defender, attackers, defenders, weight);
+            } else {
+                self.add(attackers, defenders, weight);
+            }
+        }
+    }
+
+    // Battles where `side` retreated, as (attackers, defenders, weight).
+    pub fn iter_retreats(&self, side: Side) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.get_retreats(side)
+            .iter()
+            .map(|((attackers, defenders), weight)| (*attackers, *defenders, *weight))
+    }
+
+    pub fn get_odds_of_retreat(&self, side: Side) -> f64 {
+        // Starting from zero keeps an empty sum at 0 rather than -0.
+        self.get_retreats(side)
+            .values()
+            .fold(0., |sum, weight| sum + weight)
+    }
+
+    // Chance of `side` retreating with each number of soldiers left.
+    pub fn get_retreating_results(&self, side: Side) -> Vec<f64> {
+        let mut results = match side {
+            Side::Attacker => vec![0.; self.max_attackers + 1],
+            Side::Defender => vec![0.; self.max_defenders + 1],
+        };
+        for (attackers, defenders, weight) in self.iter_retreats(side) {
+            match side {
+                Side::Attacker => results[attackers] += weight,
+                Side::Defender => results[defenders] += weight,
+            }
+        }
+        results
+    }
+
+    // Iterates over the cells holding mass as (attackers, defenders, weight).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(attackers, row)| {
+            row.iter()
+                .map(move |(defenders, weight)| (attackers, defenders, weight))
+        })
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.iter().fold(0., |sum, (_, _, weight)| sum + weight)
+    }
+
+    pub fn discard(&mut self, weight: f64) {
+        self.discarded += weight;
+    }
+
+    // Probability mass dropped by tail cutoffs and pruning so far.
+    pub fn get_discarded_mass(&self) -> f64 {
+        self.discarded
+    }
+
+    // Drops every cell below `epsilon`, counting it as discarded mass.
+    pub fn prune(&mut self, epsilon: f64) {
+        let mut pruned = 0.;
+        for weight in self.rows.iter_mut().flat_map(|row| row.values.iter_mut()) {
+            if *weight < epsilon {
+                pruned += *weight;
+                *weight = 0.;
+            }
+        }
+        self.discarded += pruned;
+    }
+
+    pub fn get_attackers_winning_results(&self) -> Vec<f64> {
+        let mut results = vec![0.; self.max_attackers + 1];
+        for (attackers, defenders, weight) in self.iter() {
+            if defenders == 0 && attackers > 0 {
+                results[attackers] += weight;
+            }
+        }
+        results
+    }
+
+    pub fn get_defenders_winning_results(&self) -> Vec<f64> {
+        let mut results = vec![0.; self.max_defenders + 1];
+        for (attackers, defenders, weight) in self.iter() {
+            if attackers == 0 && defenders > 0 {
+                results[defenders] += weight;
+            }
+        }
+        results
+    }
+
+    pub fn get_odds_of_attacker_win(&self) -> f64 {
+        self.get_attackers_winning_results().iter().sum()
+    }
+
+    pub fn get_odds_of_defender_win(&self) -> f64 {
+        self.get_defenders_winning_results().iter().sum()
+    }
+
+    // Expected soldiers left over the battles still in the grid, `None` once
+    // every battle has ended in a retreat or been discarded.
+    pub fn get_expected_attackers(&self) -> Option<f64> {
+        self.get_expected(|attackers, _| attackers)
+    }
+
+    pub fn get_expected_defenders(&self) -> Option<f64> {
+        self.get_expected(|_, defenders| defenders)
+    }
+
+    fn get_expected(&self, soldiers: impl Fn(usize, usize) -> usize) -> Option<f64> {
+        let total = self.total();
+        if total <= 0. {
+            return None;
+        }
+        let sum: f64 = self
+            .iter()
+            .map(|(attackers, defenders, weight)| soldiers(attackers, defenders) as f64 * weight)
+            .sum();
+        Some(sum / total)
+    }
+
+    // Only reachable when both sides fire at once.
+    pub fn get_odds_of_mutual_destruction(&self) -> f64 {
+        self.get(0, 0)
+    }
+
+    pub fn get_odds_of_no_win(&self) -> f64 {
+        self.iter()
+            .filter(|(attackers, defenders, _)| *attackers > 0 && *defenders > 0)
+            .fold(0., |sum, (_, _, weight)| sum + weight)
+    }
+}